@@ -0,0 +1,281 @@
+//! ToUnicode CMaps mapping character codes of PDF fonts to Unicode text.
+
+use std::collections::HashMap;
+
+/// Parsed `bfchar` and `bfrange` mappings of a ToUnicode CMap.
+#[derive(Debug, Default)]
+pub struct CMap {
+    /// Byte ranges of each code length, e.g. `<0000> <FFFF>` for 2-byte codes.
+    codespaces: Vec<(Vec<u8>, Vec<u8>)>,
+    /// (code length, code) => text
+    mappings: HashMap<(usize, u32), String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Hex(Vec<u8>),
+    ArrayStart,
+    ArrayEnd,
+    Keyword(&'a [u8]),
+}
+
+impl CMap {
+    pub fn parse(content: &[u8]) -> Self {
+        let tokens = tokenize(content);
+        let mut cmap = CMap::default();
+        let mut i = 0;
+        while i < tokens.len() {
+            match tokens[i] {
+                Token::Keyword(b"begincodespacerange") => {
+                    i += 1;
+                    while let [Token::Hex(lo), Token::Hex(hi), ..] = &tokens[i..] {
+                        cmap.codespaces.push((lo.clone(), hi.clone()));
+                        i += 2;
+                    }
+                }
+                Token::Keyword(b"beginbfchar") => {
+                    i += 1;
+                    while let [Token::Hex(src), Token::Hex(dst), ..] = &tokens[i..] {
+                        cmap.mappings.insert((src.len(), code(src)), utf16(dst));
+                        i += 2;
+                    }
+                }
+                Token::Keyword(b"beginbfrange") => {
+                    i += 1;
+                    loop {
+                        match &tokens[i..] {
+                            [Token::Hex(lo), Token::Hex(hi), Token::Hex(dst), ..] => {
+                                cmap.insert_range(lo, code(hi), |offset| {
+                                    // the last byte of the destination is incremented
+                                    let mut dst = dst.clone();
+                                    if let Some(last) = dst.last_mut() {
+                                        *last = last.wrapping_add(offset as u8);
+                                    }
+                                    Some(utf16(&dst))
+                                });
+                                i += 3;
+                            }
+                            [Token::Hex(lo), Token::Hex(hi), Token::ArrayStart, ..] => {
+                                let start = i + 3;
+                                let end = tokens[start..]
+                                    .iter()
+                                    .position(|t| *t == Token::ArrayEnd)
+                                    .map_or(tokens.len(), |p| start + p);
+                                let dsts = &tokens[start..end];
+                                cmap.insert_range(lo, code(hi), |offset| match dsts.get(offset) {
+                                    Some(Token::Hex(dst)) => Some(utf16(dst)),
+                                    _ => None,
+                                });
+                                i = end + 1;
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+        cmap
+    }
+
+    fn insert_range(&mut self, lo: &[u8], hi: u32, mut text: impl FnMut(usize) -> Option<String>) {
+        let lo_code = code(lo);
+        for (offset, c) in (lo_code..=hi.max(lo_code)).enumerate() {
+            // malformed ranges may be huge
+            if offset > 0xFFFF {
+                break;
+            }
+            if let Some(text) = text(offset) {
+                self.mappings.insert((lo.len(), c), text);
+            }
+        }
+    }
+
+    /// Length of the code at the head of bytes by codespace ranges.
+    fn code_len(&self, bytes: &[u8], default: usize) -> usize {
+        self.codespaces
+            .iter()
+            .find(|(lo, hi)| {
+                lo.len() <= bytes.len()
+                    && lo
+                        .iter()
+                        .zip(hi)
+                        .zip(bytes)
+                        .all(|((lo, hi), b)| lo <= b && b <= hi)
+            })
+            .map_or(default, |(lo, _)| lo.len())
+    }
+
+    /// Decodes a string shown by the font, `default` is the code length
+    /// if there are no matching codespace ranges, 2 for composite fonts and 1 for the others.
+    pub fn decode(&self, bytes: &[u8], default: usize) -> String {
+        self.decode_or(bytes, default, |_| String::new())
+    }
+
+    /// Decodes like [`CMap::decode`], codes without mappings are decoded by `unmapped`.
+    pub fn decode_or(
+        &self,
+        bytes: &[u8],
+        default: usize,
+        mut unmapped: impl FnMut(&[u8]) -> String,
+    ) -> String {
+        let mut text = String::new();
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let len = self.code_len(rest, default).clamp(1, rest.len());
+            let (c, tail) = rest.split_at(len);
+            match self.mappings.get(&(len, code(c))) {
+                Some(mapped) => text.push_str(mapped),
+                None => text.push_str(&unmapped(c)),
+            }
+            rest = tail;
+        }
+        text
+    }
+}
+
+fn code(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .take(4)
+        .fold(0, |code, &b| (code << 8) | b as u32)
+}
+
+fn utf16(bytes: &[u8]) -> String {
+    let units = bytes
+        .chunks(2)
+        .map(|c| match *c {
+            [hi, lo] => u16::from_be_bytes([hi, lo]),
+            [b] => b as u16,
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+}
+
+fn tokenize(content: &[u8]) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < content.len() {
+        match content[i] {
+            b'%' => {
+                while i < content.len() && content[i] != b'\n' && content[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            b'<' if content.get(i + 1) == Some(&b'<') => i += 2,
+            b'>' if content.get(i + 1) == Some(&b'>') => i += 2,
+            b'<' => {
+                let end = content[i..]
+                    .iter()
+                    .position(|&b| b == b'>')
+                    .map_or(content.len(), |p| i + p);
+                let digits = content[i + 1..end]
+                    .iter()
+                    .filter_map(|&b| (b as char).to_digit(16))
+                    .collect::<Vec<_>>();
+                let bytes = digits
+                    .chunks(2)
+                    .map(|c| (c[0] << 4 | c.get(1).copied().unwrap_or(0)) as u8)
+                    .collect();
+                tokens.push(Token::Hex(bytes));
+                i = end + 1;
+            }
+            b'[' => {
+                tokens.push(Token::ArrayStart);
+                i += 1;
+            }
+            b']' => {
+                tokens.push(Token::ArrayEnd);
+                i += 1;
+            }
+            b'(' => {
+                // literal strings are not used by mappings, just skip them
+                let mut depth = 0;
+                while i < content.len() {
+                    match content[i] {
+                        b'\\' => i += 1,
+                        b'(' => depth += 1,
+                        b')' => depth -= 1,
+                        _ => (),
+                    }
+                    i += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            b if b.is_ascii_whitespace() => i += 1,
+            _ => {
+                let end = content[i..]
+                    .iter()
+                    .position(|&b| b.is_ascii_whitespace() || b"[]<>()%/".contains(&b))
+                    .map_or(content.len(), |p| i + p.max(1));
+                tokens.push(Token::Keyword(&content[i..end]));
+                i = end;
+            }
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CMap;
+
+    const CMAP: &[u8] = br#"/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def
+/CMapName /Adobe-Identity-UCS def
+% one-byte codes for ASCII, two-byte codes for the rest
+2 begincodespacerange
+<00> <7F>
+<8000> <FFFF>
+endcodespacerange
+3 beginbfchar
+<41> <0041>
+<8001> <4E2D>
+<8002> <D835DC00>
+endbfchar
+2 beginbfrange
+<61> <63> <0061>
+<8010> <8012> [<0066006C> <00E9> <6587>]
+endbfrange
+endcmap
+CMapName currentdict /CMap defineresource pop
+end
+end"#;
+
+    #[test]
+    fn bfchar_and_bfrange() {
+        let cmap = CMap::parse(CMAP);
+        assert_eq!(cmap.decode(b"A", 2), "A");
+        // incremented ranges
+        assert_eq!(cmap.decode(b"abc", 2), "abc");
+        // surrogate pairs and arrays of destinations, e.g. ligatures
+        assert_eq!(cmap.decode(b"\x80\x01\x80\x02", 2), "中𝐀");
+        assert_eq!(cmap.decode(b"\x80\x10\x80\x11\x80\x12", 2), "flé文");
+    }
+
+    #[test]
+    fn codespace_lengths() {
+        let cmap = CMap::parse(CMAP);
+        // mixed one and two-byte codes, unmapped codes are dropped
+        assert_eq!(cmap.decode(b"a\x80\x01bZ\x80\x03c", 2), "a中bc");
+
+        // without codespace ranges, codes have the default length
+        let cmap = CMap::parse(
+            b"1 beginbfchar <0003> <0020> endbfchar 1 beginbfrange <0024> <0026> <0041> endbfrange",
+        );
+        assert_eq!(cmap.decode(b"\x00\x24\x00\x03\x00\x26", 2), "A C");
+        assert_eq!(cmap.decode(b"\x00\x24", 1), "");
+    }
+
+    #[test]
+    fn unmapped_codes() {
+        let cmap = CMap::parse(CMAP);
+        let text = cmap.decode_or(b"aZ\x80\x03", 2, |c| format!("<{:02X?}>", c));
+        assert_eq!(text, "a<[5A]><[80, 03]>");
+    }
+}
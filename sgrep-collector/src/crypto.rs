@@ -0,0 +1,102 @@
+//! Ciphers and hashes shared by decryption of Office documents and PDFs.
+
+use aes::{Aes128, Aes192, Aes256};
+use anyhow::anyhow;
+use block_modes::block_padding::{NoPadding, Pkcs7};
+use block_modes::{BlockMode, Cbc, Ecb};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hash {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Hash {
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "SHA1" | "SHA-1" => Ok(Hash::Sha1),
+            "SHA256" | "SHA-256" => Ok(Hash::Sha256),
+            "SHA384" | "SHA-384" => Ok(Hash::Sha384),
+            "SHA512" | "SHA-512" => Ok(Hash::Sha512),
+            _ => Err(anyhow!("unsupported hash algorithm {}", name)),
+        }
+    }
+
+    /// Hashes the concatenation of parts.
+    pub fn digest(self, parts: &[&[u8]]) -> Vec<u8> {
+        fn digest<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.finalize().to_vec()
+        }
+        match self {
+            Hash::Sha1 => digest::<Sha1>(parts),
+            Hash::Sha256 => digest::<Sha256>(parts),
+            Hash::Sha384 => digest::<Sha384>(parts),
+            Hash::Sha512 => digest::<Sha512>(parts),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Cbc,
+    /// CBC with PKCS#7 padding.
+    CbcPadded,
+    Ecb,
+}
+
+/// Decrypts data by AES of the key size, the IV is ignored in ECB mode.
+pub fn aes_decrypt(mode: Mode, key: &[u8], iv: &[u8], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    macro_rules! decrypt {
+        ($cipher:ty) => {
+            match mode {
+                Mode::Cbc => {
+                    Cbc::<$cipher, NoPadding>::new_from_slices(key, iv)?.decrypt_vec(data)?
+                }
+                Mode::CbcPadded => {
+                    Cbc::<$cipher, Pkcs7>::new_from_slices(key, iv)?.decrypt_vec(data)?
+                }
+                Mode::Ecb => {
+                    Ecb::<$cipher, NoPadding>::new_from_slices(key, &[])?.decrypt_vec(data)?
+                }
+            }
+        };
+    }
+    Ok(match key.len() {
+        16 => decrypt!(Aes128),
+        24 => decrypt!(Aes192),
+        32 => decrypt!(Aes256),
+        len => return Err(anyhow!("invalid AES key length {}", len)),
+    })
+}
+
+/// Encrypts data by AES-128-CBC without padding.
+pub fn aes128_cbc_encrypt(key: &[u8], iv: &[u8], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    Ok(Cbc::<Aes128, NoPadding>::new_from_slices(key, iv)?.encrypt_vec(data))
+}
+
+/// RC4 stream cipher, encryption and decryption are the same.
+pub fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut s = (0..=255).collect::<Vec<u8>>();
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+        s.swap(i, j as usize);
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|&b| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(s[i as usize]);
+            s.swap(i as usize, j as usize);
+            b ^ s[s[i as usize].wrapping_add(s[j as usize]) as usize]
+        })
+        .collect()
+}
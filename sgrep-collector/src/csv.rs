@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use ::csv::{ReaderBuilder, StringRecord, Trim};
+use tracing::{debug, instrument, warn};
+
+use crate::utf8::read_text;
+use crate::{Collector, Line};
+
+const EXTENSIONS: [&str; 3] = ["csv", "tsv", "tab"];
+const DELIMITERS: [u8; 4] = [b',', b'\t', b';', b'|'];
+/// Lines and records sampled to detect the delimiter and the header.
+const SAMPLE_ROWS: usize = 20;
+
+/// Collects records of delimiter-separated files as `row 120`,
+/// fields are named by the header row if there is one.
+#[derive(Debug, Clone, Copy)]
+pub struct CsvCollector;
+
+impl Collector for CsvCollector {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        extension.map_or(false, |e| EXTENSIONS.contains(&e))
+    }
+
+    fn is_text(&self) -> bool {
+        true
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        let text = read_text(path)?;
+        let default = match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => b',',
+            _ => b'\t',
+        };
+        let delimiter = delimiter(&text).unwrap_or(default);
+        let mut records = Vec::new();
+        let reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .trim(Trim::All)
+            .from_reader(text.as_bytes());
+        // malformed rows are skipped but still counted in positions
+        for (i, record) in reader.into_records().enumerate() {
+            match record {
+                Ok(record) => records.push((i, record)),
+                Err(err) => warn!("skip row {} of {:?}: {}", i + 1, path, err),
+            }
+        }
+        let rows = records.iter().map(|(_, record)| record).collect::<Vec<_>>();
+        let header = matches!(records.first(), Some((0, _)) if is_header(&rows))
+            .then(|| records[0].1.clone());
+        debug!(
+            "collect {} records delimited by {:?}, header: {:?}",
+            records.len(),
+            delimiter as char,
+            header
+        );
+
+        Ok(records
+            .iter()
+            .filter(|(_, record)| record.iter().any(|field| !field.is_empty()))
+            .map(|(i, record)| {
+                let columns = match header {
+                    Some(ref header) if *i > 0 => record
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, field)| !field.is_empty())
+                        .map(|(c, field)| {
+                            let name = match header.get(c) {
+                                Some(name) if !name.is_empty() => name.to_string(),
+                                _ => format!("column{}", c + 1),
+                            };
+                            (name, field.to_string())
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                let line = if columns.is_empty() {
+                    record.iter().collect::<Vec<_>>().join(" | ")
+                } else {
+                    columns
+                        .iter()
+                        .map(|(name, field)| format!("{}: {}", name, field))
+                        .collect::<Vec<_>>()
+                        .join(" | ")
+                };
+                Line {
+                    position: format!("row {}", i + 1),
+                    line,
+                    columns,
+                    ..Default::default()
+                }
+            })
+            .collect())
+    }
+}
+
+/// Detects the delimiter occurring the same times in most sampled lines, quoted ones are ignored.
+fn delimiter(text: &str) -> Option<u8> {
+    let lines = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .take(SAMPLE_ROWS)
+        .collect::<Vec<_>>();
+    // the first one wins ties, as `max_by_key` returns the last maximum
+    DELIMITERS
+        .iter()
+        .rev()
+        .filter_map(|&delimiter| {
+            let counts = lines
+                .iter()
+                .map(|line| {
+                    let mut quoted = false;
+                    line.bytes()
+                        .filter(|&b| {
+                            if b == b'"' {
+                                quoted = !quoted;
+                            }
+                            b == delimiter && !quoted
+                        })
+                        .count()
+                })
+                .collect::<Vec<_>>();
+            let first = *counts.first()?;
+            let consistent = counts.iter().filter(|&&count| count == first).count();
+            (first > 0).then(|| ((consistent, first), delimiter))
+        })
+        .max_by_key(|&(score, _)| score)
+        .map(|(_, delimiter)| delimiter)
+}
+
+/// Guesses whether the first record is a header: its fields are distinct non-numeric names,
+/// and some column is numeric below it or its names don't repeat in their columns.
+fn is_header(records: &[&StringRecord]) -> bool {
+    let (header, rows) = match records {
+        [header, rows @ ..] if !rows.is_empty() => (header, &rows[..rows.len().min(SAMPLE_ROWS)]),
+        _ => return false,
+    };
+    let mut names = HashSet::new();
+    let valid = header
+        .iter()
+        .all(|name| !name.is_empty() && !is_number(name) && names.insert(name));
+    if !valid {
+        return false;
+    }
+    let numeric = (0..header.len()).any(|c| {
+        rows.iter()
+            .filter_map(|row| row.get(c).filter(|field| !field.is_empty()))
+            .all(is_number)
+            && rows.iter().any(|row| row.get(c).map_or(false, is_number))
+    });
+    let repeated = header
+        .iter()
+        .enumerate()
+        .any(|(c, name)| rows.iter().any(|row| row.get(c) == Some(name)));
+    numeric || !repeated
+}
+
+fn is_number(field: &str) -> bool {
+    field
+        .trim_start_matches(|c| c == '$' || c == '¥' || c == '€')
+        .trim_end_matches('%')
+        .replace(',', "")
+        .parse::<f64>()
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::delimiter;
+
+    #[test]
+    fn detect_delimiter() {
+        assert_eq!(delimiter("name;email\nAlice;a@example.com\n"), Some(b';'));
+        // quoted delimiters are ignored
+        assert_eq!(delimiter("a|b\n\"x|y\"|z\n"), Some(b'|'));
+        assert_eq!(delimiter("name\nAlice\n"), None);
+    }
+
+    #[test]
+    fn first_delimiter_wins_ties() {
+        assert_eq!(delimiter("a;b,c\nd;e,f\n"), Some(b','));
+        assert_eq!(delimiter("a|b\tc\nd|e\tf\n"), Some(b'\t'));
+    }
+}
@@ -0,0 +1,246 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::instrument;
+
+use crate::utf8::read_text;
+use crate::{Collector, Line};
+
+const EXTENSIONS: [&str; 4] = ["json", "yaml", "yml", "toml"];
+
+/// Collects scalars of JSON, YAML and TOML files located by their key paths,
+/// e.g. `spec.template.containers[0].image`, keys with `.` or `[` are quoted like `labels["app.kind"]`,
+/// documents of multi-document YAML are prefixed like `doc2:`.
+/// Lines are `key: value` and the value is searchable by the key as a column, e.g. `image:nginx`.
+#[derive(Debug, Clone, Copy)]
+pub struct DataCollector;
+
+impl Collector for DataCollector {
+    fn name(&self) -> &'static str {
+        "data"
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        extension.map_or(false, |e| EXTENSIONS.contains(&e))
+    }
+
+    fn is_text(&self) -> bool {
+        true
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        let text = read_text(path)?;
+        data_lines(&text, path.extension().and_then(|e| e.to_str()))
+    }
+}
+
+/// Lines of all documents in the text, formats other than JSON and TOML are parsed as YAML.
+fn data_lines(text: &str, extension: Option<&str>) -> anyhow::Result<Vec<Line>> {
+    let documents = match extension {
+        Some("json") => vec![serde_json::from_str(text)?],
+        Some("toml") => vec![from_toml(text.parse()?)],
+        _ => serde_yaml::Deserializer::from_str(text)
+            .map(|document| Ok(from_yaml(serde_yaml::Value::deserialize(document)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?,
+    };
+
+    let mut lines = Vec::new();
+    let multiple = documents.len() > 1;
+    for (i, document) in documents.iter().enumerate() {
+        // empty documents, e.g. of a trailing `---`
+        if document.is_null() {
+            continue;
+        }
+        let path = if multiple {
+            format!("doc{}:", i + 1)
+        } else {
+            String::new()
+        };
+        walk(document, path, "", &mut lines);
+    }
+    Ok(lines)
+}
+
+/// Collects scalars under the value, `key` is the name of the nearest enclosing key.
+fn walk(value: &Value, path: String, key: &str, lines: &mut Vec<Line>) {
+    let scalar = match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                let path = if k.contains(|c| c == '.' || c == '[') {
+                    format!("{}[{:?}]", path, k)
+                } else if path.is_empty() || path.ends_with(':') {
+                    format!("{}{}", path, k)
+                } else {
+                    format!("{}.{}", path, k)
+                };
+                walk(v, path, k, lines);
+            }
+            return;
+        }
+        Value::Array(values) => {
+            for (i, v) in values.iter().enumerate() {
+                walk(v, format!("{}[{}]", path, i), key, lines);
+            }
+            return;
+        }
+        Value::String(s) if s.trim().is_empty() => return,
+        Value::String(s) => s.clone(),
+        Value::Null => "null".to_string(),
+        value => value.to_string(),
+    };
+    let (line, columns) = if key.is_empty() {
+        (scalar, Vec::new())
+    } else {
+        (
+            format!("{}: {}", key, scalar),
+            vec![(key.to_string(), scalar)],
+        )
+    };
+    lines.push(Line {
+        position: if path.is_empty() {
+            "$".to_string()
+        } else {
+            path
+        },
+        line,
+        columns,
+        ..Default::default()
+    });
+}
+
+fn from_yaml(value: serde_yaml::Value) -> Value {
+    match value {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(b) => Value::Bool(b),
+        serde_yaml::Value::Number(n) => serde_json::to_value(&n)
+            .ok()
+            .filter(|n| !n.is_null())
+            .unwrap_or_else(|| Value::String(n.to_string())),
+        serde_yaml::Value::String(s) => Value::String(s),
+        serde_yaml::Value::Sequence(values) => values.into_iter().map(from_yaml).collect(),
+        serde_yaml::Value::Mapping(mapping) => Value::Object(
+            mapping
+                .into_iter()
+                .map(|(k, v)| {
+                    let key = match from_yaml(k) {
+                        Value::String(s) => s,
+                        key => key.to_string(),
+                    };
+                    (key, from_yaml(v))
+                })
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
+
+fn from_toml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => serde_json::Number::from_f64(f)
+            .map_or_else(|| Value::String(f.to_string()), Value::Number),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(values) => values.into_iter().map(from_toml).collect(),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, from_toml(v)))
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::data_lines;
+    use crate::tests::lines;
+    use crate::Line;
+
+    fn collect(text: &str, extension: &str) -> Vec<Line> {
+        data_lines(text, Some(extension)).unwrap()
+    }
+
+    #[test]
+    fn key_paths() {
+        let json =
+            r#"{"spec": {"containers": [{"image": "nginx", "ports": [80, 443]}]}, "debug": null}"#;
+        assert_eq!(
+            lines(&collect(json, "json")),
+            [
+                ("spec.containers[0].image", "image: nginx"),
+                ("spec.containers[0].ports[0]", "ports: 80"),
+                ("spec.containers[0].ports[1]", "ports: 443"),
+                ("debug", "debug: null"),
+            ]
+        );
+        assert_eq!(lines(&collect("\"scalar\"", "json")), [("$", "scalar")]);
+        assert_eq!(
+            lines(&collect(
+                r#"{"labels": {"app.kubernetes.io/name": "web", "a[0]": "b"}}"#,
+                "json"
+            )),
+            [
+                (
+                    r#"labels["app.kubernetes.io/name"]"#,
+                    "app.kubernetes.io/name: web"
+                ),
+                (r#"labels["a[0]"]"#, "a[0]: b"),
+            ]
+        );
+
+        let toml = "title = \"Notes\"\n[owner]\nname = \"Alice\"\ndob = 1979-05-27T07:32:00Z\n";
+        assert_eq!(
+            lines(&collect(toml, "toml")),
+            [
+                ("title", "title: Notes"),
+                ("owner.name", "name: Alice"),
+                ("owner.dob", "dob: 1979-05-27T07:32:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn columns() {
+        let lines = collect("name: Alice\ntags: [a, b]\n", "yaml");
+        let columns = lines
+            .iter()
+            .flat_map(|l| l.columns.iter().cloned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            vec![
+                ("name".to_string(), "Alice".to_string()),
+                ("tags".to_string(), "a".to_string()),
+                ("tags".to_string(), "b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn yaml_documents() {
+        let yaml = "kind: Service\nspec:\n  port: 80\n---\nkind: Deployment\n1.5: float key\n";
+        assert_eq!(
+            lines(&collect(yaml, "yml")),
+            [
+                ("doc1:kind", "kind: Service"),
+                ("doc1:spec.port", "port: 80"),
+                ("doc2:kind", "kind: Deployment"),
+                ("doc2:[\"1.5\"]", "1.5: float key"),
+            ]
+        );
+        // a single document isn't prefixed
+        assert_eq!(
+            lines(&collect("---\nkind: Service\n", "yaml")),
+            [("kind", "kind: Service")]
+        );
+        // empty documents are skipped
+        assert_eq!(
+            lines(&collect("kind: Service\n---\n", "yaml")),
+            [("doc1:kind", "kind: Service")]
+        );
+        assert!(collect("", "yaml").is_empty());
+    }
+}
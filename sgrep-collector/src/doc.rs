@@ -0,0 +1,295 @@
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::anyhow;
+use encoding_rs::{UTF_16LE, WINDOWS_1252};
+use tracing::{debug, instrument};
+
+use crate::{Collector, Encrypted, Line};
+
+const WORD_DOCUMENT: &str = "/WordDocument";
+const IDENT: u16 = 0xA5EC;
+/// Index of `fcClx` in `FibRgFcLcb97`.
+const CLX_INDEX: usize = 33;
+/// Stories following the main document in the text stream, with their `ccp*` offsets in FibRgLw97.
+const STORIES: [(&str, usize); 6] = [
+    ("footnote", 16),
+    ("header", 20),
+    ("comment", 28),
+    ("endnote", 32),
+    ("textbox", 36),
+    ("header-textbox", 40),
+];
+
+/// Collects legacy Word 97-2003 documents by the piece table of the WordDocument stream.
+#[derive(Debug, Clone, Copy)]
+pub struct DocCollector;
+
+impl Collector for DocCollector {
+    fn name(&self) -> &'static str {
+        "doc"
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        matches!(extension, Some(e) if e == "doc")
+    }
+
+    fn accept_mime_type(&self, mime_type: &str) -> bool {
+        mime_type == "application/msword"
+    }
+
+    /// Collects paragraphs of the main document as `p42`,
+    /// paragraphs of footnotes, headers, comments, ... as `footnote:p3`.
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        let mut file = cfb::open(path)?;
+        let mut word = Vec::new();
+        file.open_stream(WORD_DOCUMENT)?.read_to_end(&mut word)?;
+        let fib = Fib::parse(&word)?;
+        let table_name = if fib.table1 { "/1Table" } else { "/0Table" };
+        let mut table = Vec::new();
+        file.open_stream(table_name)?.read_to_end(&mut table)?;
+
+        let text = text(&word, &table, &fib)?.chars().collect::<Vec<_>>();
+        let mut lines = paragraphs(&text[..fib.ccp_text.min(text.len())])
+            .map(|(i, line)| Line {
+                position: format!("p{}", i),
+                line,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let mut start = fib.ccp_text;
+        for (story, ccp) in fib.stories {
+            let end = (start + ccp).min(text.len());
+            if start < end {
+                debug!("collect {} of {} characters", story, ccp);
+                lines.extend(paragraphs(&text[start..end]).map(|(i, line)| Line {
+                    position: format!("{}:p{}", story, i),
+                    line,
+                    ..Default::default()
+                }));
+            }
+            start += ccp;
+        }
+        Ok(lines)
+    }
+}
+
+/// Fields of the File Information Block needed to extract text.
+struct Fib {
+    /// Whether the table stream is `1Table` rather than `0Table`.
+    table1: bool,
+    ccp_text: usize,
+    stories: Vec<(&'static str, usize)>,
+    fc_clx: usize,
+    lcb_clx: usize,
+}
+
+impl Fib {
+    fn parse(word: &[u8]) -> anyhow::Result<Self> {
+        if u16_at(word, 0)? != IDENT {
+            return Err(anyhow!("not a Word 97-2003 document"));
+        }
+        let flags = u16_at(word, 0x0A)?;
+        // fEncrypted, reported like other encrypted files though RC4 decryption isn't supported
+        if flags & 0x0100 != 0 {
+            return Err(Encrypted::NoPassword.into());
+        }
+        // FibBase (32 bytes), then the variable-sized FibRgW, FibRgLw and FibRgFcLcb
+        let csw = u16_at(word, 32)? as usize;
+        let rg_lw = 34 + csw * 2 + 2;
+        let cslw = u16_at(word, rg_lw - 2)? as usize;
+        let rg_fc_lcb = rg_lw + cslw * 4 + 2;
+        let fc_clx = rg_fc_lcb + CLX_INDEX * 8;
+        let mut stories = Vec::with_capacity(STORIES.len());
+        for (story, offset) in STORIES {
+            stories.push((story, u32_at(word, rg_lw + offset)? as usize));
+        }
+        Ok(Self {
+            table1: flags & 0x0200 != 0,
+            ccp_text: u32_at(word, rg_lw + 12)? as usize,
+            stories,
+            fc_clx: u32_at(word, fc_clx)? as usize,
+            lcb_clx: u32_at(word, fc_clx + 4)? as usize,
+        })
+    }
+}
+
+/// Concatenates all pieces of the piece table in the Clx of the table stream.
+fn text(word: &[u8], table: &[u8], fib: &Fib) -> anyhow::Result<String> {
+    let clx = table
+        .get(fib.fc_clx..fib.fc_clx + fib.lcb_clx)
+        .ok_or_else(|| anyhow!("invalid Clx"))?;
+    // skip Prc entries of property modifiers
+    let mut offset = 0;
+    while clx.get(offset) == Some(&0x01) {
+        offset += 3 + u16_at(clx, offset + 1)? as usize;
+    }
+    if clx.get(offset) != Some(&0x02) {
+        return Err(anyhow!("piece table not found"));
+    }
+    let lcb = u32_at(clx, offset + 1)? as usize;
+    let plc = clx
+        .get(offset + 5..offset + 5 + lcb)
+        .ok_or_else(|| anyhow!("invalid piece table"))?;
+
+    // PlcPcd: n + 1 character positions followed by n 8-byte piece descriptors
+    let n = plc.len().saturating_sub(4) / 12;
+    let mut text = String::new();
+    for i in 0..n {
+        let (start, end) = (u32_at(plc, i * 4)?, u32_at(plc, i * 4 + 4)?);
+        let count = end.saturating_sub(start) as usize;
+        let fc = u32_at(plc, (n + 1) * 4 + i * 8 + 2)?;
+        let piece = if fc & 0x4000_0000 != 0 {
+            // compressed pieces are 8-bit Windows-1252 at half the offset
+            let offset = (fc & 0x3FFF_FFFF) as usize / 2;
+            let bytes = word
+                .get(offset..offset + count)
+                .ok_or_else(|| anyhow!("invalid piece {}", i))?;
+            WINDOWS_1252.decode_without_bom_handling(bytes).0
+        } else {
+            let offset = fc as usize;
+            let bytes = word
+                .get(offset..offset + count * 2)
+                .ok_or_else(|| anyhow!("invalid piece {}", i))?;
+            UTF_16LE.decode_without_bom_handling(bytes).0
+        };
+        text.push_str(&piece);
+    }
+    Ok(text)
+}
+
+/// Non-empty paragraphs with their 1-based indexes, control characters are stripped
+/// and only results of fields are kept.
+fn paragraphs(text: &[char]) -> impl '_ + Iterator<Item = (usize, String)> {
+    let mut fields = Vec::new();
+    text.split(|&c| c == '\r')
+        .enumerate()
+        .filter_map(move |(i, paragraph)| {
+            let mut line = String::new();
+            for &c in paragraph {
+                match c {
+                    // field begin, separator and end, codes between begin and separator are skipped
+                    '\u{13}' => fields.push(true),
+                    '\u{14}' => {
+                        if let Some(code) = fields.last_mut() {
+                            *code = false;
+                        }
+                    }
+                    '\u{15}' => {
+                        fields.pop();
+                    }
+                    _ if fields.last() == Some(&true) => (),
+                    // cell marks, line breaks, page breaks and tabs
+                    '\u{07}' | '\u{0B}' | '\u{0C}' | '\t' | '\u{A0}' => line.push(' '),
+                    '\u{1E}' => line.push('-'),
+                    c if c.is_control() || c == '\u{1F}' => (),
+                    c => line.push(c),
+                }
+            }
+            let line = line.trim();
+            (!line.is_empty()).then(|| (i + 1, line.to_string()))
+        })
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> anyhow::Result<u16> {
+    let bytes = bytes
+        .get(offset..offset + 2)
+        .ok_or_else(|| anyhow!("unexpected end at {}", offset))?;
+    Ok(u16::from_le_bytes(bytes.try_into()?))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let bytes = bytes
+        .get(offset..offset + 4)
+        .ok_or_else(|| anyhow!("unexpected end at {}", offset))?;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{paragraphs, text, Fib, IDENT};
+    use crate::Encrypted;
+
+    /// WordDocument stream with a FIB of Word 97 followed by the text.
+    fn word(ccp_text: u32, ccp_ftn: u32, lcb_clx: u32, text: &[u8]) -> Vec<u8> {
+        let mut word = vec![0; 32];
+        word[..2].copy_from_slice(&IDENT.to_le_bytes());
+        // 1Table
+        word[0x0A..0x0C].copy_from_slice(&0x0200u16.to_le_bytes());
+        word.extend(14u16.to_le_bytes());
+        word.extend([0; 28]);
+        word.extend(22u16.to_le_bytes());
+        let rg_lw = word.len();
+        word.extend([0; 88]);
+        word[rg_lw + 12..rg_lw + 16].copy_from_slice(&ccp_text.to_le_bytes());
+        word[rg_lw + 16..rg_lw + 20].copy_from_slice(&ccp_ftn.to_le_bytes());
+        word.extend(93u16.to_le_bytes());
+        let rg_fc_lcb = word.len();
+        word.extend([0; 93 * 8]);
+        // fcClx is 0, the Clx is at the start of the table stream
+        word[rg_fc_lcb + 33 * 8 + 4..rg_fc_lcb + 33 * 8 + 8]
+            .copy_from_slice(&lcb_clx.to_le_bytes());
+        word.extend(text);
+        word
+    }
+
+    #[test]
+    fn piece_table() {
+        let compressed = b"Hello\rWorld\r";
+        let unicode = "Müller\rNote\r"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        let start = word(0, 0, 0, &[]).len() as u32;
+
+        // a Prc of property modifiers, then the Pcdt
+        let mut clx = vec![0x01, 2, 0, 0xFF, 0xFF, 0x02];
+        clx.extend((3 * 4 + 2 * 8u32).to_le_bytes());
+        for cp in [0u32, 12, 24] {
+            clx.extend(cp.to_le_bytes());
+        }
+        for fc in [(start * 2) | 0x4000_0000, start + 12] {
+            clx.extend([0, 0]);
+            clx.extend(fc.to_le_bytes());
+            clx.extend([0, 0]);
+        }
+        let mut content = compressed.to_vec();
+        content.extend(unicode);
+        let word = word(19, 5, clx.len() as u32, &content);
+
+        let fib = Fib::parse(&word).unwrap();
+        assert!(fib.table1);
+        assert_eq!(fib.ccp_text, 19);
+        assert_eq!(fib.stories[0], ("footnote", 5));
+        assert_eq!(
+            text(&word, &clx, &fib).unwrap(),
+            "Hello\rWorld\rMüller\rNote\r"
+        );
+    }
+
+    #[test]
+    fn not_a_document() {
+        assert!(Fib::parse(b"PK\x03\x04").is_err());
+        let mut word = word(0, 0, 0, &[]);
+        word[0x0A..0x0C].copy_from_slice(&0x0100u16.to_le_bytes());
+        let err = Fib::parse(&word).err().unwrap();
+        assert_eq!(err.downcast_ref(), Some(&Encrypted::NoPassword));
+    }
+
+    #[test]
+    fn field_results_and_marks() {
+        let text = "Intro\r\rSee \u{13} HYPERLINK \"https://example.com\" \u{14}the site\u{15}.\rA\u{07}B\u{07}\u{07}\rnon\u{1E}breaking\u{1F}hy\u{0C}\r"
+            .chars()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paragraphs(&text).collect::<Vec<_>>(),
+            vec![
+                (1, "Intro".to_string()),
+                (3, "See the site.".to_string()),
+                (4, "A B".to_string()),
+                (5, "non-breakinghy".to_string()),
+            ]
+        );
+    }
+}
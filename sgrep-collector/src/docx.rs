@@ -1,10 +1,27 @@
-use std::io::{BufRead, BufReader};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
 use std::path::Path;
 
-use dotext::{Docx, MsDoc};
-use tracing::instrument;
+use anyhow::anyhow;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use tracing::{debug, instrument};
+use zip::ZipArchive;
 
-use crate::{Collector, Line};
+use crate::package::{read_entry, relationships, Relationship};
+use crate::{office_crypto, Collector, Encrypted, Line};
+
+const DOCUMENT: &str = "word/document.xml";
+/// Parts referenced by the main document, collected after the body.
+const PARTS: [&str; 5] = ["header", "footer", "footnotes", "endnotes", "comments"];
+/// Elements of footnotes, endnotes and comments, identified by `w:id`.
+const NOTES: [&[u8]; 3] = [b"w:footnote", b"w:endnote", b"w:comment"];
+/// References of footnotes and endnotes in the main document, numbered in order of appearance.
+const REFERENCES: [(&str, &[u8]); 2] = [
+    ("footnotes", b"w:footnoteReference"),
+    ("endnotes", b"w:endnoteReference"),
+];
 
 #[derive(Debug, Clone, Copy)]
 pub struct DocxCollector;
@@ -15,22 +32,428 @@ impl Collector for DocxCollector {
     }
 
     fn accept_extension(&self, extension: Option<&str>) -> bool {
-        matches!(extension, Some(e) if e == "docx" || e == "doc")
+        matches!(extension, Some(e) if e == "docx")
+    }
+
+    fn accept_mime_type(&self, mime_type: &str) -> bool {
+        mime_type == "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
     }
 
+    /// Collects paragraphs of the body located by the nearest heading, e.g. `p42 (§2.3 Design)`,
+    /// then headers (`header1`), footers, footnotes (`footnote3`), endnotes and comments.
     #[instrument]
     fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
-        let mut doc = Docx::open(path)?;
-        let buffered = BufReader::new(&mut doc);
-        buffered
-            .lines()
-            .enumerate()
-            .map(|(_, line)| {
-                Ok(Line {
-                    position: "".to_string(), // TODO: locate lines
-                    line: line?,
-                })
-            })
-            .collect()
+        if office_crypto::is_encrypted(path)? {
+            return Err(Encrypted::NoPassword.into());
+        }
+        collect_package(ZipArchive::new(File::open(path)?)?)
+    }
+
+    #[instrument(skip(password))]
+    fn collect_encrypted(&self, path: &Path, password: &str) -> anyhow::Result<Vec<Line>> {
+        let package = office_crypto::decrypt(path, password)?;
+        collect_package(ZipArchive::new(Cursor::new(package))?)
+    }
+}
+
+fn collect_package<R: Read + Seek>(mut archive: ZipArchive<R>) -> anyhow::Result<Vec<Line>> {
+    let document = relationships(&mut archive, "")?
+        .into_iter()
+        .find(|rel| kind(rel) == "officeDocument")
+        .map_or_else(|| DOCUMENT.to_string(), |rel| rel.target);
+    let rels = relationships(&mut archive, &document)?;
+    let styles = match rels.iter().find(|rel| kind(rel) == "styles") {
+        Some(rel) => match read_entry(&mut archive, &rel.target)? {
+            Some(content) => heading_styles(&content)?,
+            None => HashMap::new(),
+        },
+        None => HashMap::new(),
+    };
+    let content =
+        read_entry(&mut archive, &document)?.ok_or_else(|| anyhow!("{} not found", document))?;
+
+    let numbers = note_numbers(&content)?;
+    let paragraphs = paragraphs(&content, &styles)?;
+    // number headings from the top level in use
+    let top = paragraphs.iter().filter_map(|p| p.level).min().unwrap_or(1);
+    let mut outline = Outline::default();
+    let mut lines = Vec::new();
+    for p in paragraphs {
+        if let Some(level) = p.level {
+            outline.enter(level + 1 - top, &p.text);
+        }
+        lines.push(Line {
+            position: match outline.heading {
+                Some(ref heading) => format!("p{} ({})", p.index, heading),
+                None => format!("p{}", p.index),
+            },
+            line: p.text,
+            ..Default::default()
+        });
+    }
+
+    for part in PARTS {
+        let targets = rels.iter().filter(|rel| kind(rel) == part);
+        for (i, rel) in targets.enumerate() {
+            debug!("collect {} from {}", part, rel.target);
+            let content = match read_entry(&mut archive, &rel.target)? {
+                Some(content) => content,
+                None => continue,
+            };
+            lines.extend(paragraphs(&content, &styles)?.into_iter().map(|p| Line {
+                position: match p.note {
+                    // footnotes => footnote3, by the displayed number if referenced
+                    Some(id) => match numbers.get(&(part, id.clone())) {
+                        Some(number) => format!("{}{}", part.trim_end_matches('s'), number),
+                        None => format!("{}{}", part.trim_end_matches('s'), id),
+                    },
+                    None => format!("{}{}", part, i + 1),
+                },
+                line: p.text,
+                ..Default::default()
+            }));
+        }
+    }
+    Ok(lines)
+}
+
+/// Last segment of the relationship type, e.g. `header` of
+/// `http://schemas.openxmlformats.org/officeDocument/2006/relationships/header`.
+fn kind(rel: &Relationship) -> &str {
+    rel.kind.rsplit('/').next().unwrap_or_default()
+}
+
+/// Displayed numbers of footnotes and endnotes by part and `w:id`,
+/// ids are arbitrary and also count separators.
+fn note_numbers(content: &str) -> anyhow::Result<HashMap<(&'static str, String), usize>> {
+    let mut reader = Reader::from_str(content);
+    let mut buf = Vec::new();
+    let mut numbers = HashMap::new();
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Empty(ref e) | Event::Start(ref e) => {
+                if let Some((part, _)) = REFERENCES.iter().find(|(_, name)| *name == e.name()) {
+                    if let Some(id) = attribute(&reader, e, b"w:id")? {
+                        let next = numbers.keys().filter(|(p, _)| p == part).count() + 1;
+                        numbers.entry((*part, id)).or_insert(next);
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(numbers)
+}
+
+#[derive(Debug)]
+struct Paragraph {
+    /// 1-based index of the paragraph in its part, empty paragraphs are counted.
+    index: usize,
+    /// Outline level of headings, 1 for `Heading 1`.
+    level: Option<usize>,
+    /// `w:id` of the enclosing footnote, endnote or comment.
+    note: Option<String>,
+    text: String,
+}
+
+/// Hierarchical numbers of headings, e.g. `§2.3 Design`.
+#[derive(Default)]
+struct Outline {
+    numbers: Vec<usize>,
+    heading: Option<String>,
+}
+
+impl Outline {
+    fn enter(&mut self, level: usize, title: &str) {
+        self.numbers.resize(level, 0);
+        self.numbers[level - 1] += 1;
+        let numbers = self
+            .numbers
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        self.heading = Some(format!("§{} {}", numbers.join("."), title));
+    }
+}
+
+/// Non-empty paragraphs of a WordprocessingML part, including those in tables and text boxes.
+fn paragraphs(content: &str, styles: &HashMap<String, usize>) -> anyhow::Result<Vec<Paragraph>> {
+    let mut reader = Reader::from_str(content);
+    let mut buf = Vec::new();
+    let mut skipped = Vec::new();
+    let mut paragraphs = Vec::new();
+    // whether a `mc:Choice` of the current `mc:AlternateContent` was read
+    let mut choice = false;
+    // paragraphs of text boxes are nested in runs of the outer paragraph
+    let mut stack: Vec<(Option<usize>, String)> = Vec::new();
+    let mut index = 0;
+    let mut note = None;
+    let (mut in_run, mut in_text) = (false, false);
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) if NOTES.contains(&e.name()) => {
+                note = attribute(&reader, e, b"w:id")?;
+            }
+            Event::End(ref e) if NOTES.contains(&e.name()) => note = None,
+            Event::Start(ref e) if e.name() == b"mc:Choice" => choice = true,
+            // the fallback repeats the choice, e.g. a VML copy of a DrawingML text box
+            Event::Start(ref e) if e.name() == b"mc:Fallback" && choice => {
+                reader.read_to_end(b"mc:Fallback", &mut skipped)?;
+                skipped.clear();
+            }
+            Event::End(ref e) if e.name() == b"mc:AlternateContent" => choice = false,
+            Event::Start(ref e) if e.name() == b"w:p" => stack.push((None, String::new())),
+            Event::End(ref e) if e.name() == b"w:p" => {
+                index += 1;
+                let (level, paragraph) = stack.pop().unwrap_or_default();
+                let text = paragraph.trim();
+                if !text.is_empty() {
+                    paragraphs.push(Paragraph {
+                        index,
+                        level,
+                        note: note.clone(),
+                        text: text.to_string(),
+                    });
+                }
+            }
+            Event::Start(ref e) if e.name() == b"w:r" => in_run = true,
+            Event::End(ref e) if e.name() == b"w:r" => in_run = false,
+            Event::Start(ref e) if e.name() == b"w:t" => in_text = true,
+            Event::End(ref e) if e.name() == b"w:t" => in_text = false,
+            Event::Text(ref e) if in_text => {
+                if let Some((_, paragraph)) = stack.last_mut() {
+                    paragraph.push_str(&e.unescape_and_decode(&reader)?);
+                }
+            }
+            Event::Empty(ref e) => {
+                if let Some((level, paragraph)) = stack.last_mut() {
+                    match e.name() {
+                        b"w:pStyle" => {
+                            if let Some(style) = attribute(&reader, e, b"w:val")? {
+                                *level = level.or_else(|| styles.get(&style).copied());
+                            }
+                        }
+                        b"w:outlineLvl" => {
+                            *level = outline_level(&reader, e)?.or(*level);
+                        }
+                        b"w:tab" | b"w:br" | b"w:cr" if in_run => paragraph.push(' '),
+                        b"w:noBreakHyphen" if in_run => paragraph.push('-'),
+                        _ => (),
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(paragraphs)
+}
+
+/// Outline levels of heading styles in styles.xml by style id,
+/// localized names like `Überschrift 1` are covered by `w:outlineLvl` or `w:basedOn`.
+fn heading_styles(content: &str) -> anyhow::Result<HashMap<String, usize>> {
+    let mut reader = Reader::from_str(content);
+    let mut buf = Vec::new();
+    // style id => (level, based on)
+    let mut styles: HashMap<String, (Option<usize>, Option<String>)> = HashMap::new();
+    let mut style = None;
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) if e.name() == b"w:style" => {
+                style = attribute(&reader, e, b"w:styleId")?;
+                if let Some(ref id) = style {
+                    styles.insert(id.clone(), (None, None));
+                }
+            }
+            Event::End(ref e) if e.name() == b"w:style" => style = None,
+            Event::Empty(ref e) => {
+                if let Some((level, based_on)) = style.as_ref().and_then(|id| styles.get_mut(id)) {
+                    match e.name() {
+                        b"w:name" => {
+                            let name = attribute(&reader, e, b"w:val")?.unwrap_or_default();
+                            let heading = name.to_lowercase();
+                            if let Some(n) = heading.strip_prefix("heading ") {
+                                *level = level.or_else(|| n.parse().ok());
+                            }
+                        }
+                        b"w:outlineLvl" => *level = outline_level(&reader, e)?,
+                        b"w:basedOn" => *based_on = attribute(&reader, e, b"w:val")?,
+                        _ => (),
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    let mut levels = HashMap::new();
+    for id in styles.keys() {
+        let mut current = id;
+        // limit the depth in case of cyclic inheritance
+        for _ in 0..styles.len() {
+            match styles.get(current) {
+                Some((Some(level), _)) => {
+                    levels.insert(id.clone(), *level);
+                    break;
+                }
+                Some((None, Some(parent))) => current = parent,
+                _ => break,
+            }
+        }
+    }
+    Ok(levels)
+}
+
+/// `w:outlineLvl` is 0-based and 9 means body text.
+fn outline_level<B: std::io::BufRead>(
+    reader: &Reader<B>,
+    element: &BytesStart,
+) -> anyhow::Result<Option<usize>> {
+    Ok(attribute(reader, element, b"w:val")?
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|&v| v < 9)
+        .map(|v| v + 1))
+}
+
+fn attribute<B: std::io::BufRead>(
+    reader: &Reader<B>,
+    element: &BytesStart,
+    key: &[u8],
+) -> anyhow::Result<Option<String>> {
+    for attr in element.attributes() {
+        let attr = attr?;
+        if attr.key == key {
+            return Ok(Some(attr.unescape_and_decode_value(reader)?));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{heading_styles, note_numbers, paragraphs, Outline};
+
+    #[test]
+    fn outline_numbers() {
+        let mut outline = Outline::default();
+        let headings = [
+            (1, "Intro"),
+            (1, "Design"),
+            (2, "Parser"),
+            (2, "Index"),
+            (3, "Terms"),
+            (1, "Usage"),
+            (2, "CLI"),
+        ]
+        .into_iter()
+        .map(|(level, title)| {
+            outline.enter(level, title);
+            outline.heading.clone().unwrap()
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(
+            headings,
+            vec![
+                "§1 Intro",
+                "§2 Design",
+                "§2.1 Parser",
+                "§2.2 Index",
+                "§2.2.1 Terms",
+                "§3 Usage",
+                "§3.1 CLI",
+            ]
+        );
+    }
+
+    #[test]
+    fn styles_and_paragraphs() {
+        let styles = heading_styles(
+            r#"<w:styles>
+                <w:style w:styleId="Normal"><w:name w:val="Normal"/></w:style>
+                <w:style w:styleId="Heading1"><w:name w:val="heading 1"/></w:style>
+                <w:style w:styleId="berschrift2"><w:name w:val="Überschrift 2"/><w:pPr><w:outlineLvl w:val="1"/></w:pPr></w:style>
+                <w:style w:styleId="Title1"><w:name w:val="Chapter"/><w:basedOn w:val="Heading1"/></w:style>
+            </w:styles>"#,
+        )
+        .unwrap();
+        assert_eq!(styles.get("Heading1"), Some(&1));
+        assert_eq!(styles.get("berschrift2"), Some(&2));
+        assert_eq!(styles.get("Title1"), Some(&1));
+        assert_eq!(styles.get("Normal"), None);
+
+        let paragraphs = paragraphs(
+            r#"<w:document><w:body>
+                <w:p><w:pPr><w:pStyle w:val="Title1"/></w:pPr><w:r><w:t>Design</w:t></w:r></w:p>
+                <w:p></w:p>
+                <w:p><w:pPr><w:pStyle w:val="berschrift2"/></w:pPr><w:r><w:t>Parser</w:t></w:r></w:p>
+                <w:p><w:r><w:t>tokens</w:t><w:tab/><w:t>and terms</w:t></w:r></w:p>
+            </w:body></w:document>"#,
+            &styles,
+        )
+        .unwrap();
+        let paragraphs = paragraphs
+            .iter()
+            .map(|p| (p.index, p.level, p.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paragraphs,
+            vec![
+                (1, Some(1), "Design"),
+                (3, Some(2), "Parser"),
+                (4, None, "tokens and terms"),
+            ]
+        );
+    }
+
+    #[test]
+    fn alternate_content() {
+        let paragraphs = paragraphs(
+            r#"<w:document><w:body>
+                <w:p><w:r><mc:AlternateContent>
+                    <mc:Choice Requires="wps"><w:txbxContent><w:p><w:r><w:t>boxed</w:t></w:r></w:p></w:txbxContent></mc:Choice>
+                    <mc:Fallback><w:pict><w:txbxContent><w:p><w:r><w:t>boxed</w:t></w:r></w:p></w:txbxContent></w:pict></mc:Fallback>
+                </mc:AlternateContent></w:r></w:p>
+                <w:p><w:r><mc:AlternateContent>
+                    <mc:Choice Requires="unknown"/>
+                    <mc:Fallback><w:t>fallback</w:t></mc:Fallback>
+                </mc:AlternateContent></w:r></w:p>
+            </w:body></w:document>"#,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let texts = paragraphs
+            .iter()
+            .map(|p| p.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["boxed", "fallback"]);
+    }
+
+    #[test]
+    fn displayed_note_numbers() {
+        let numbers = note_numbers(
+            r#"<w:document><w:body>
+                <w:p><w:r><w:t>first</w:t></w:r><w:r><w:footnoteReference w:id="7"/></w:r></w:p>
+                <w:p><w:r><w:endnoteReference w:id="2"/></w:r><w:r><w:footnoteReference w:id="3"/></w:r></w:p>
+                <w:p><w:r><w:footnoteReference w:id="7"/></w:r></w:p>
+            </w:body></w:document>"#,
+        )
+        .unwrap();
+        let mut numbers = numbers.into_iter().collect::<Vec<_>>();
+        numbers.sort();
+        assert_eq!(
+            numbers,
+            vec![
+                (("endnotes", "2".to_string()), 1),
+                (("footnotes", "3".to_string()), 2),
+                (("footnotes", "7".to_string()), 1),
+            ]
+        );
     }
 }
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use anyhow::anyhow;
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::Reader;
+use tracing::{debug, instrument};
+use zip::ZipArchive;
+
+use crate::package::{read_entry, resolve};
+use crate::{Collector, Line};
+
+const CONTAINER: &str = "META-INF/container.xml";
+const BLOCKS: [&[u8]; 17] = [
+    b"p",
+    b"h1",
+    b"h2",
+    b"h3",
+    b"h4",
+    b"h5",
+    b"h6",
+    b"li",
+    b"dt",
+    b"dd",
+    b"td",
+    b"th",
+    b"pre",
+    b"blockquote",
+    b"figcaption",
+    b"caption",
+    b"div",
+];
+const SKIPPED: [&[u8]; 3] = [b"head", b"script", b"style"];
+
+#[derive(Debug, Clone, Copy)]
+pub struct EpubCollector;
+
+impl Collector for EpubCollector {
+    fn name(&self) -> &'static str {
+        "epub"
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        matches!(extension, Some(e) if e == "epub")
+    }
+
+    fn accept_mime_type(&self, mime_type: &str) -> bool {
+        mime_type == "application/epub+zip"
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        chapter_lines(&mut ZipArchive::new(File::open(path)?)?)
+    }
+}
+
+/// Paragraphs of chapters in reading order as `ch03:p12 (Chapter title)`.
+fn chapter_lines<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<Vec<Line>> {
+    let opf = rootfile(archive)?.ok_or_else(|| anyhow!("rootfile not found"))?;
+    let package = Package::parse(
+        &opf,
+        &read_entry(archive, &opf)?.ok_or_else(|| anyhow!("{} not found", opf))?,
+    )?;
+    let titles = package.titles(archive)?;
+
+    let mut lines = Vec::new();
+    for (i, chapter) in package.spine().enumerate() {
+        let content = match read_entry(archive, chapter)? {
+            Some(content) => content,
+            None => continue,
+        };
+        debug!("collect chapter {} from {}", i + 1, chapter);
+        let title = titles.get(chapter);
+        for (j, paragraph) in paragraphs(&content)?.into_iter().enumerate() {
+            let position = format!("ch{:02}:p{}", i + 1, j + 1);
+            lines.push(Line {
+                position: match title {
+                    Some(t) => format!("{} ({})", position, t),
+                    None => position,
+                },
+                line: paragraph,
+                ..Default::default()
+            });
+        }
+    }
+    Ok(lines)
+}
+
+#[derive(Debug, Default)]
+struct Item {
+    href: String,
+    media_type: String,
+    properties: String,
+}
+
+/// Manifest and spine of the OPF package document.
+#[derive(Debug, Default)]
+struct Package {
+    manifest: HashMap<String, Item>,
+    spine: Vec<String>,
+    toc: Option<String>,
+}
+
+impl Package {
+    fn parse(opf: &str, content: &str) -> anyhow::Result<Self> {
+        let mut reader = Reader::from_str(content);
+        let mut buf = Vec::new();
+        let mut package = Package::default();
+        loop {
+            match reader.read_event(&mut buf)? {
+                Event::Start(ref e) | Event::Empty(ref e) => match e.local_name() {
+                    b"item" => {
+                        let mut id = String::new();
+                        let mut item = Item::default();
+                        for attr in e.attributes() {
+                            let attr = attr?;
+                            let value = attr.unescape_and_decode_value(&reader)?;
+                            match attr.key {
+                                b"id" => id = value,
+                                b"href" => item.href = resolve(opf, &value),
+                                b"media-type" => item.media_type = value,
+                                b"properties" => item.properties = value,
+                                _ => (),
+                            }
+                        }
+                        package.manifest.insert(id, item);
+                    }
+                    b"spine" => package.toc = attribute(&reader, e, b"toc")?,
+                    b"itemref" => {
+                        if let Some(idref) = attribute(&reader, e, b"idref")? {
+                            package.spine.push(idref);
+                        }
+                    }
+                    _ => (),
+                },
+                Event::Eof => break,
+                _ => (),
+            }
+            buf.clear();
+        }
+        Ok(package)
+    }
+
+    /// Part names of the spine in reading order.
+    fn spine(&self) -> impl Iterator<Item = &str> {
+        self.spine
+            .iter()
+            .filter_map(|id| self.manifest.get(id))
+            .map(|item| item.href.as_str())
+    }
+
+    /// Chapter titles by part name, from the EPUB 3 navigation document or the EPUB 2 NCX.
+    fn titles<R: Read + Seek>(
+        &self,
+        archive: &mut ZipArchive<R>,
+    ) -> anyhow::Result<HashMap<String, String>> {
+        let nav = self
+            .manifest
+            .values()
+            .find(|item| item.properties.split_whitespace().any(|p| p == "nav"));
+        let ncx = self
+            .toc
+            .as_ref()
+            .and_then(|id| self.manifest.get(id))
+            .or_else(|| {
+                self.manifest
+                    .values()
+                    .find(|item| item.media_type == "application/x-dtbncx+xml")
+            });
+        let (toc, label, link, href) = match (nav, ncx) {
+            (Some(nav), _) => (nav, &b"a"[..], &b"a"[..], &b"href"[..]),
+            (None, Some(ncx)) => (ncx, &b"text"[..], &b"content"[..], &b"src"[..]),
+            (None, None) => return Ok(HashMap::new()),
+        };
+        let content = match read_entry(archive, &toc.href)? {
+            Some(content) => content,
+            None => return Ok(HashMap::new()),
+        };
+
+        let mut reader = Reader::from_str(&content);
+        reader.check_end_names(false);
+        let mut buf = Vec::new();
+        let mut titles = HashMap::new();
+        let (mut target, mut capture, mut title) = (None, None, None);
+        loop {
+            match reader.read_event(&mut buf)? {
+                Event::Start(ref e) | Event::Empty(ref e) => {
+                    if e.local_name() == link {
+                        if let Some(h) = attribute(&reader, e, href)? {
+                            let part = h.split('#').next().unwrap_or_default();
+                            target = Some(resolve(&toc.href, part));
+                        }
+                    }
+                    if e.local_name() == label {
+                        capture = Some(String::new());
+                    }
+                }
+                Event::Text(ref e) => {
+                    if let Some(c) = capture.as_mut() {
+                        c.push_str(&text(&reader, e));
+                    }
+                }
+                Event::End(ref e) if e.local_name() == label => title = capture.take(),
+                Event::Eof => break,
+                _ => (),
+            }
+            // the NCX label precedes its link while the navigation document nests it
+            if let (Some(t), Some(l)) = (target.as_ref(), title.as_ref()) {
+                titles.entry(t.clone()).or_insert_with(|| normalize(l));
+                target = None;
+                title = None;
+            }
+            buf.clear();
+        }
+        titles.retain(|_, title| !title.is_empty());
+        Ok(titles)
+    }
+}
+
+/// Path of the OPF package document declared in META-INF/container.xml.
+fn rootfile<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<Option<String>> {
+    let content = match read_entry(archive, CONTAINER)? {
+        Some(content) => content,
+        None => return Ok(None),
+    };
+    let mut reader = Reader::from_str(&content);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) | Event::Empty(ref e) if e.local_name() == b"rootfile" => {
+                return attribute(&reader, e, b"full-path");
+            }
+            Event::Eof => return Ok(None),
+            _ => (),
+        }
+        buf.clear();
+    }
+}
+
+/// Text of block-level elements of an XHTML content document.
+fn paragraphs(content: &str) -> anyhow::Result<Vec<String>> {
+    let mut reader = Reader::from_str(content);
+    reader.check_end_names(false);
+    let mut buf = Vec::new();
+    let mut paragraphs = Vec::new();
+    // blocks may be nested (e.g. `<li><p>`), text belongs to the innermost one
+    let mut stack: Vec<String> = Vec::new();
+    let mut skipped = 0usize;
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) if SKIPPED.contains(&e.local_name()) => skipped += 1,
+            Event::End(ref e) if SKIPPED.contains(&e.local_name()) => {
+                skipped = skipped.saturating_sub(1)
+            }
+            Event::Start(ref e) if BLOCKS.contains(&e.local_name()) => stack.push(String::new()),
+            Event::End(ref e) if BLOCKS.contains(&e.local_name()) => {
+                if let Some(paragraph) = stack.pop() {
+                    let paragraph = normalize(&paragraph);
+                    if !paragraph.is_empty() {
+                        paragraphs.push(paragraph);
+                    }
+                }
+            }
+            Event::Empty(ref e) if e.local_name() == b"br" => {
+                if let Some(paragraph) = stack.last_mut() {
+                    paragraph.push(' ');
+                }
+            }
+            Event::Text(ref e) | Event::CData(ref e) if skipped == 0 => {
+                match stack.last_mut() {
+                    Some(paragraph) => paragraph.push_str(&text(&reader, e)),
+                    None => {
+                        // text outside any block, e.g. directly in `<body>`
+                        let t = normalize(&text(&reader, e));
+                        if !t.is_empty() {
+                            paragraphs.push(t);
+                        }
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(paragraphs)
+}
+
+fn attribute<B: std::io::BufRead>(
+    reader: &Reader<B>,
+    element: &BytesStart,
+    key: &[u8],
+) -> anyhow::Result<Option<String>> {
+    for attr in element.attributes() {
+        let attr = attr?;
+        if attr.key == key {
+            return Ok(Some(attr.unescape_and_decode_value(reader)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Unescapes text, keeping it raw if it contains unknown entities.
+fn text<B: std::io::BufRead>(reader: &Reader<B>, e: &BytesText) -> String {
+    e.unescape_and_decode(reader)
+        .unwrap_or_else(|_| String::from_utf8_lossy(e.escaped()).into_owned())
+}
+
+/// Collapses whitespace, markup indentation should not be indexed.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::chapter_lines;
+    use crate::package::archive;
+    use crate::tests::lines;
+
+    #[test]
+    fn chapters_in_spine_order() {
+        let mut archive = archive(&[
+            (
+                "META-INF/container.xml",
+                r#"<container><rootfiles>
+                    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+                </rootfiles></container>"#,
+            ),
+            (
+                "OEBPS/content.opf",
+                r#"<package><manifest>
+                    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+                    <item id="c1" href="text/one.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="c2" href="text/two.xhtml" media-type="application/xhtml+xml"/>
+                </manifest><spine><itemref idref="c2"/><itemref idref="c1"/></spine></package>"#,
+            ),
+            (
+                "OEBPS/nav.xhtml",
+                r#"<html><body><nav><ol>
+                    <li><a href="text/two.xhtml#start">  The
+                        Beginning </a></li>
+                </ol></nav></body></html>"#,
+            ),
+            (
+                "OEBPS/text/one.xhtml",
+                "<html><head><title>One</title></head><body><p>Later</p></body></html>",
+            ),
+            (
+                "OEBPS/text/two.xhtml",
+                "<html><body><h1>Start</h1><ul><li><p>First<br/>line</p></li></ul>\
+                 <script>skipped()</script><p></p><p>Next</p></body></html>",
+            ),
+        ]);
+        assert_eq!(
+            lines(&chapter_lines(&mut archive).unwrap()),
+            [
+                ("ch01:p1 (The Beginning)", "Start"),
+                ("ch01:p2 (The Beginning)", "First line"),
+                ("ch01:p3 (The Beginning)", "Next"),
+                ("ch02:p1", "Later"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{
+    BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+};
+use tracing::instrument;
+
+use crate::{Collector, Line};
+
+const EXTENSIONS: [&str; 4] = ["html", "htm", "xhtml", "xht"];
+const INLINE_TAGS: [&str; 26] = [
+    "a", "abbr", "b", "bdi", "bdo", "cite", "code", "data", "del", "dfn", "em", "font", "i", "ins",
+    "kbd", "label", "mark", "q", "s", "samp", "small", "span", "strong", "sub", "sup", "u",
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct HtmlCollector;
+
+impl Collector for HtmlCollector {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        extension.map_or(false, |e| EXTENSIONS.contains(&e))
+    }
+
+    fn accept_mime_type(&self, mime_type: &str) -> bool {
+        mime_type == "text/html"
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        let content = std::fs::read(path)?;
+        Ok(visible_text(&String::from_utf8_lossy(&content)))
+    }
+}
+
+/// Visible text of an HTML document, positioned by source line numbers.
+pub fn visible_text(content: &str) -> Vec<Line> {
+    let mut input = BufferQueue::new();
+    input.push_back(StrTendril::from_slice(content));
+    let mut tokenizer = Tokenizer::new(TextSink::default(), TokenizerOpts::default());
+    let _ = tokenizer.feed(&mut input);
+    tokenizer.end();
+    tokenizer.sink.lines
+}
+
+/// Collects visible text between block-level tags, positioned by the source line it starts at.
+#[derive(Debug, Default)]
+struct TextSink {
+    lines: Vec<Line>,
+    text: String,
+    start: Option<u64>,
+    in_raw: bool,
+}
+
+impl TextSink {
+    fn flush(&mut self, position: Option<&str>) {
+        let text = self.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if let Some(start) = self.start.take() {
+            if !text.is_empty() {
+                self.lines.push(Line {
+                    position: position
+                        .map(ToString::to_string)
+                        .unwrap_or_else(|| start.to_string()),
+                    line: text,
+                    ..Default::default()
+                });
+            }
+        }
+        self.text.clear();
+    }
+
+    fn process_tag(&mut self, tag: Tag) -> TokenSinkResult<()> {
+        let start = tag.kind == TagKind::StartTag;
+        match &*tag.name {
+            "title" if start => {
+                self.flush(None);
+                return TokenSinkResult::RawData(RawKind::Rcdata);
+            }
+            "title" => self.flush(Some("title")),
+            "script" | "style" | "noembed" | "noframes" | "template" if start => {
+                self.flush(None);
+                if !tag.self_closing {
+                    self.in_raw = true;
+                    return TokenSinkResult::RawData(if &*tag.name == "script" {
+                        RawKind::ScriptData
+                    } else {
+                        RawKind::Rawtext
+                    });
+                }
+            }
+            "script" | "style" | "noembed" | "noframes" | "template" => self.in_raw = false,
+            "meta" => {
+                let attr = |name: &str| {
+                    tag.attrs
+                        .iter()
+                        .find(|a| &*a.name.local == name)
+                        .map(|a| a.value.to_string())
+                };
+                let description = attr("name")
+                    .filter(|n| n.eq_ignore_ascii_case("description"))
+                    .and(attr("content"));
+                if let Some(description) = description {
+                    self.lines.push(Line {
+                        position: "description".to_string(),
+                        line: description,
+                        ..Default::default()
+                    });
+                }
+            }
+            name if INLINE_TAGS.contains(&name) => (),
+            "br" | "img" | "wbr" => self.text.push(' '),
+            _ => self.flush(None),
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+impl TokenSink for TextSink {
+    type Handle = ();
+
+    fn process_token(&mut self, token: Token, line_number: u64) -> TokenSinkResult<()> {
+        match token {
+            Token::TagToken(tag) => return self.process_tag(tag),
+            Token::CharacterTokens(text) if !self.in_raw => {
+                if self.start.is_none() && !text.trim().is_empty() {
+                    self.start = Some(line_number);
+                }
+                if self.start.is_some() {
+                    self.text.push_str(&text);
+                }
+            }
+            Token::EOFToken => self.flush(None),
+            _ => (),
+        }
+        TokenSinkResult::Continue
+    }
+}
@@ -1,32 +1,135 @@
-#![feature(string_remove_matches)]
 #![feature(box_syntax)]
 #![feature(bool_to_option)]
 
+mod cmap;
+mod crypto;
+mod csv;
+mod data;
+mod doc;
 mod docx;
+mod epub;
+mod html;
+mod mail;
+mod markdown;
+mod notebook;
+mod odf;
+mod office_crypto;
+mod package;
 mod pdf;
+mod pdf_crypto;
+mod pptx;
 mod sheet;
+mod source;
+mod subtitle;
 mod utf8;
 
+use std::fmt::{self, Display};
 use std::path::Path;
 
+pub use self::csv::CsvCollector;
+pub use self::data::DataCollector;
+pub use self::doc::DocCollector;
 pub use self::docx::DocxCollector;
+pub use self::epub::EpubCollector;
+pub use self::html::HtmlCollector;
+pub use self::mail::MailCollector;
+pub use self::markdown::MarkdownCollector;
+pub use self::notebook::NotebookCollector;
+pub use self::odf::OdfCollector;
 pub use self::pdf::PDFCollector;
+pub use self::pptx::PptxCollector;
 pub use self::sheet::SheetCollector;
-pub use self::utf8::UTF8Collector;
+pub use self::source::{split_identifiers, SourceCollector};
+pub use self::subtitle::SubtitleCollector;
+pub use self::utf8::{sample, UTF8Collector};
 
-pub fn all_collectors() -> Vec<Box<dyn Collector>> {
+/// Options of collectors.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Collects rows of sheets joined with the header row instead of cells.
+    pub sheet_rows: bool,
+    /// Collects code blocks of Markdown files.
+    pub markdown_code: bool,
+    /// Indexes sub-tokens of `snake_case` and `CamelCase` identifiers in source files.
+    pub source_split_identifiers: bool,
+    /// Collects comments and docstrings of source files only.
+    pub source_comments: bool,
+}
+
+/// All collectors in dispatching order.
+pub fn all_collectors(config: &Config) -> Vec<Box<dyn Collector>> {
     vec![
         box DocxCollector,
-        box SheetCollector,
+        box DocCollector,
+        box SheetCollector {
+            rows: config.sheet_rows,
+        },
+        box CsvCollector,
+        box MarkdownCollector {
+            code: config.markdown_code,
+        },
+        box NotebookCollector,
+        box DataCollector,
+        box SourceCollector {
+            split_identifiers: config.source_split_identifiers,
+            comments: config.source_comments,
+        },
+        box SubtitleCollector,
+        box PptxCollector,
+        box OdfCollector,
+        box EpubCollector,
+        box HtmlCollector,
+        box MailCollector,
         box PDFCollector,
         box UTF8Collector,
     ]
 }
 
+/// Content type of a file sniffed by magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContentType {
+    pub mime_type: &'static str,
+    /// Canonical extension of the content type, e.g. `docx`.
+    pub extension: &'static str,
+}
+
+/// Sniffs the content type from the `sample` of the head of a file.
+pub fn sniff(sample: &[u8]) -> Option<ContentType> {
+    infer::get(sample).map(|t| ContentType {
+        mime_type: t.mime_type(),
+        extension: t.extension(),
+    })
+}
+
+/// Error of encrypted files, collectors return `NoPassword` from `collect` so that
+/// they can be collected again by `collect_encrypted` with passwords.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encrypted {
+    NoPassword,
+    WrongPassword,
+}
+
+impl Display for Encrypted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encrypted::NoPassword => write!(f, "encrypted, no password"),
+            Encrypted::WrongPassword => write!(f, "encrypted, wrong password"),
+        }
+    }
+}
+
+impl std::error::Error for Encrypted {}
+
 #[derive(Debug, Clone, Default)]
 pub struct Line {
     pub position: String,
     pub line: String,
+    /// Named values of structured records, e.g. (`email`, `alice@example.com`) of a CSV row,
+    /// searchable by `email:alice`.
+    pub columns: Vec<(String, String)>,
+    /// Extra terms the line is searchable by but not shown, e.g. sub-tokens `index` and `files`
+    /// of the identifier `index_files`.
+    pub terms: Vec<String>,
 }
 
 pub trait Collector: Sync + Send {
@@ -38,8 +141,65 @@ pub trait Collector: Sync + Send {
         true
     }
 
+    /// Whether the collector accepts content sniffed as `mime_type`, regardless of the extension.
+    fn accept_mime_type(&self, _mime_type: &str) -> bool {
+        false
+    }
+
+    /// Fallback collectors accepting almost everything are only tried after all the others.
+    fn is_fallback(&self) -> bool {
+        false
+    }
+
     fn should_collect(&self, path: &Path) -> anyhow::Result<bool> {
         let extension = path.extension().and_then(|e| e.to_str());
         Ok(self.accept_extension(extension))
     }
+
+    /// Text collectors read files of any encoding by `read_text`.
+    fn is_text(&self) -> bool {
+        false
+    }
+
+    /// Character encoding of the file detected from the `sample` of its head,
+    /// `None` if it's not a text file or the collector doesn't read text.
+    fn encoding(&self, sample: &[u8]) -> Option<&'static str> {
+        self.is_text()
+            .then(|| sample)
+            .and_then(utf8::detect)
+            .map(|e| e.name())
+    }
+
+    /// Options changing the collected lines, files collected by the collector
+    /// are indexed again once they change.
+    fn config_digest(&self) -> String {
+        String::new()
+    }
+
+    /// Collects an encrypted file with the password,
+    /// returns [`Encrypted::WrongPassword`] if it doesn't match.
+    fn collect_encrypted(&self, _path: &Path, _password: &str) -> anyhow::Result<Vec<Line>> {
+        Err(Encrypted::NoPassword.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Collector, CsvCollector, DocxCollector, Line, SubtitleCollector};
+
+    /// Positions and text of lines, to be compared with expected pairs.
+    pub fn lines(lines: &[Line]) -> Vec<(&str, &str)> {
+        lines
+            .iter()
+            .map(|l| (l.position.as_str(), l.line.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn encodings_of_text_collectors() {
+        assert_eq!(CsvCollector.encoding(b"name,email\n"), Some("UTF-8"));
+        assert_eq!(SubtitleCollector.encoding(b"\xFF\xFE1\0"), Some("UTF-16LE"));
+        assert_eq!(CsvCollector.encoding(b"\x7fELF\x02\x01\x01\0\0\0"), None);
+        assert_eq!(DocxCollector.encoding(b"plain text"), None);
+    }
 }
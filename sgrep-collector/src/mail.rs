@@ -0,0 +1,164 @@
+use std::path::Path;
+
+use mailparse::{parse_mail, DispositionType, MailHeaderMap, ParsedMail};
+use rayon::prelude::*;
+use tracing::{instrument, warn};
+
+use crate::html::visible_text;
+use crate::{Collector, Line};
+
+const EXTENSIONS: [&str; 3] = ["eml", "mbox", "mbx"];
+const HEADERS: [&str; 5] = ["Subject", "From", "To", "Cc", "Date"];
+
+#[derive(Debug, Clone, Copy)]
+pub struct MailCollector;
+
+impl Collector for MailCollector {
+    fn name(&self) -> &'static str {
+        "mail"
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        extension.map_or(false, |e| EXTENSIONS.contains(&e))
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        let content = std::fs::read(path)?;
+        let messages = match path.extension().and_then(|e| e.to_str()) {
+            Some("eml") => vec![content],
+            _ => split_mbox(&content),
+        };
+        let results = messages
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, raw)| collect_message(&format!("msg{}", i + 1), &raw))
+            .collect::<Vec<_>>();
+        // a broken message of a mailbox doesn't fail the others
+        let mut lines = Vec::new();
+        let mut first_error = None;
+        let mut parsed = false;
+        for (i, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(message) => {
+                    parsed = true;
+                    lines.extend(message);
+                }
+                Err(err) => {
+                    warn!("skip message {} of {:?}: {:#}", i + 1, path, err);
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        match first_error {
+            Some(err) if !parsed => Err(err),
+            _ => Ok(lines),
+        }
+    }
+}
+
+fn collect_message(prefix: &str, raw: &[u8]) -> anyhow::Result<Vec<Line>> {
+    let mail = parse_mail(raw)?;
+    let mut lines = HEADERS
+        .iter()
+        .filter_map(|&h| {
+            Some(Line {
+                position: format!("{}:{}", prefix, h.to_lowercase()),
+                line: mail.headers.get_first_value(h)?,
+                ..Default::default()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut texts = Vec::new();
+    collect_texts(&mail, &mut texts)?;
+    let mut offset = 0;
+    for text in texts {
+        let body_lines = match text {
+            Text::Plain(body) => body
+                .lines()
+                .enumerate()
+                .map(|(i, l)| (i + 1, l.to_string()))
+                .collect::<Vec<_>>(),
+            Text::Html(body) => visible_text(&body)
+                .into_iter()
+                .filter_map(|l| Some((l.position.parse().ok()?, l.line)))
+                .collect(),
+        };
+        let mut last = 0;
+        for (i, line) in body_lines {
+            last = i;
+            if !line.trim().is_empty() {
+                lines.push(Line {
+                    position: format!("{}:L{}", prefix, offset + i),
+                    line,
+                    ..Default::default()
+                });
+            }
+        }
+        // number lines of subsequent parts after the previous ones
+        offset += last;
+    }
+    Ok(lines)
+}
+
+enum Text {
+    Plain(String),
+    Html(String),
+}
+
+/// Decoded text bodies of a message, attachments are skipped and
+/// `text/plain` is preferred over `text/html` in alternatives.
+fn collect_texts(mail: &ParsedMail, texts: &mut Vec<Text>) -> anyhow::Result<()> {
+    if matches!(
+        mail.get_content_disposition().disposition,
+        DispositionType::Attachment
+    ) {
+        return Ok(());
+    }
+    let mimetype = mail.ctype.mimetype.as_str();
+    if mimetype == "multipart/alternative" {
+        let plain = mail
+            .subparts
+            .iter()
+            .any(|p| p.ctype.mimetype == "text/plain");
+        for part in mail
+            .subparts
+            .iter()
+            .filter(|p| !plain || p.ctype.mimetype != "text/html")
+        {
+            collect_texts(part, texts)?;
+        }
+    } else if !mail.subparts.is_empty() {
+        for part in mail.subparts.iter() {
+            collect_texts(part, texts)?;
+        }
+    } else if mimetype == "text/plain" {
+        texts.push(Text::Plain(mail.get_body()?));
+    } else if mimetype == "text/html" {
+        texts.push(Text::Html(mail.get_body()?));
+    }
+    Ok(())
+}
+
+/// Splits an mbox archive into raw messages by `From ` separator lines,
+/// unescaping `>From ` quoted lines (mboxrd).
+fn split_mbox(content: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut message: Option<Vec<u8>> = None;
+    let mut blank = true;
+    for line in content.split_inclusive(|&b| b == b'\n') {
+        if blank && line.starts_with(b"From ") {
+            messages.extend(message.replace(Vec::new()));
+            continue;
+        }
+        blank = line.iter().all(|b| b.is_ascii_whitespace());
+        let current = message.get_or_insert_with(Vec::new);
+        match line.iter().position(|&b| b != b'>') {
+            Some(i) if i > 0 && line[i..].starts_with(b"From ") => current.extend(&line[1..]),
+            _ => current.extend(line),
+        }
+    }
+    messages.extend(message);
+    messages
+}
@@ -0,0 +1,281 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use tracing::instrument;
+
+use crate::utf8::read_text;
+use crate::{Collector, Line};
+
+const EXTENSIONS: [&str; 4] = ["md", "markdown", "mdown", "mkd"];
+
+/// Collects Markdown rendered to plain text, blocks are located by their first line
+/// and the enclosing headings, e.g. `L57 (# Setup > ## Linux)`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownCollector {
+    /// Collects lines of code blocks, which are skipped by default.
+    pub code: bool,
+}
+
+impl Collector for MarkdownCollector {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn config_digest(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        extension.map_or(false, |e| EXTENSIONS.contains(&e))
+    }
+
+    fn is_text(&self) -> bool {
+        true
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        let text = read_text(path)?;
+        Ok(self.render(&text))
+    }
+}
+
+impl MarkdownCollector {
+    /// Renders Markdown text to located lines.
+    pub(crate) fn render(&self, text: &str) -> Vec<Line> {
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_FOOTNOTES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS;
+        let text = &blank_front_matter(text);
+        let mut renderer = Renderer::new(text);
+        let mut code = false;
+        for (event, range) in Parser::new_ext(text, options).into_offset_iter() {
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => {
+                    renderer.flush();
+                    code = true;
+                }
+                Event::End(Tag::CodeBlock(_)) => code = false,
+                Event::Text(text) if code => {
+                    if self.code {
+                        renderer.code(&text, range);
+                    }
+                }
+                Event::Start(Tag::Heading(..)) => renderer.flush(),
+                Event::End(Tag::Heading(level, ..)) => renderer.heading(level as usize),
+                Event::Start(tag) | Event::End(tag) if is_block(&tag) => renderer.flush(),
+                Event::End(Tag::TableCell) => renderer.block.push_str(" | "),
+                Event::Text(text) | Event::Code(text) => renderer.push(&text, range),
+                Event::SoftBreak | Event::HardBreak => renderer.block.push(' '),
+                Event::TaskListMarker(done) => {
+                    renderer.block.push_str(if done { "[x] " } else { "[ ] " })
+                }
+                _ => (),
+            }
+        }
+        renderer.flush();
+        renderer.lines
+    }
+}
+
+/// Replaces YAML (`---`) or TOML (`+++`) front matter with empty lines, which would be
+/// rendered as a setext heading otherwise, line numbers of the rest are kept.
+fn blank_front_matter(text: &str) -> Cow<'_, str> {
+    let first = text.split_inclusive('\n').next().unwrap_or_default();
+    let fence = first.trim_end();
+    if fence != "---" && fence != "+++" {
+        return Cow::Borrowed(text);
+    }
+    let mut end = first.len();
+    for line in text[end..].split_inclusive('\n') {
+        end += line.len();
+        let line = line.trim_end();
+        if line == fence || (fence == "---" && line == "...") {
+            let blank = "\n".repeat(text[..end].matches('\n').count());
+            return Cow::Owned(blank + &text[end..]);
+        }
+    }
+    Cow::Borrowed(text)
+}
+
+fn is_block(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Paragraph
+            | Tag::BlockQuote
+            | Tag::List(_)
+            | Tag::Item
+            | Tag::FootnoteDefinition(_)
+            | Tag::Table(_)
+            | Tag::TableHead
+            | Tag::TableRow
+    )
+}
+
+struct Renderer<'a> {
+    text: &'a str,
+    /// Byte offsets of line starts.
+    line_starts: Vec<usize>,
+    /// Levels and titles of enclosing headings.
+    headings: Vec<(usize, String)>,
+    /// Text of the current block and the offset it starts at.
+    block: String,
+    start: Option<usize>,
+    lines: Vec<Line>,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            text,
+            line_starts,
+            headings: Vec::new(),
+            block: String::new(),
+            start: None,
+            lines: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, text: &str, range: Range<usize>) {
+        self.start.get_or_insert(range.start);
+        self.block.push_str(text);
+    }
+
+    /// Collects lines of a code block separately.
+    fn code(&mut self, code: &str, range: Range<usize>) {
+        let first = self.line_number(range.start);
+        for (i, line) in code.lines().enumerate() {
+            if !line.trim().is_empty() {
+                self.emit(first + i, line.trim_end().to_string());
+            }
+        }
+    }
+
+    fn heading(&mut self, level: usize) {
+        let title = self.block.split_whitespace().collect::<Vec<_>>().join(" ");
+        while matches!(self.headings.last(), Some((l, _)) if *l >= level) {
+            self.headings.pop();
+        }
+        self.headings.push((level, title));
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        let block = std::mem::take(&mut self.block);
+        let block = block.split_whitespace().collect::<Vec<_>>().join(" ");
+        let block = block.trim_end_matches(" |").to_string();
+        if let Some(start) = self.start.take() {
+            if !block.is_empty() {
+                self.emit(self.line_number(start), block);
+            }
+        }
+    }
+
+    fn emit(&mut self, number: usize, line: String) {
+        let position = if self.headings.is_empty() {
+            format!("L{}", number)
+        } else {
+            let path = self
+                .headings
+                .iter()
+                .map(|(level, title)| format!("{} {}", "#".repeat(*level), title))
+                .collect::<Vec<_>>();
+            format!("L{} ({})", number, path.join(" > "))
+        };
+        self.lines.push(Line {
+            position,
+            line,
+            ..Default::default()
+        });
+    }
+
+    /// 1-based number of the line containing the offset.
+    fn line_number(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset.min(self.text.len())) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MarkdownCollector;
+    use crate::tests::lines;
+
+    const TEXT: &str = "Intro paragraph
+with a soft break.
+
+# Setup
+
+## Linux
+
+Run `make`.
+
+```sh
+make install
+```
+
+## macOS
+
+- [x] brew
+
+# Usage
+
+| name | value |
+|------|-------|
+| a    | 1     |
+";
+
+    #[test]
+    fn heading_paths() {
+        assert_eq!(
+            lines(&MarkdownCollector::default().render(TEXT)),
+            [
+                ("L1", "Intro paragraph with a soft break."),
+                ("L4 (# Setup)", "Setup"),
+                ("L6 (# Setup > ## Linux)", "Linux"),
+                ("L8 (# Setup > ## Linux)", "Run make."),
+                ("L14 (# Setup > ## macOS)", "macOS"),
+                ("L16 (# Setup > ## macOS)", "[x] brew"),
+                ("L18 (# Usage)", "Usage"),
+                ("L20 (# Usage)", "name | value"),
+                ("L22 (# Usage)", "a | 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn code_blocks() {
+        let without = MarkdownCollector::default().render(TEXT);
+        assert!(!lines(&without).iter().any(|&(_, l)| l == "make install"));
+        let with = MarkdownCollector { code: true }.render(TEXT);
+        assert!(lines(&with).contains(&("L11 (# Setup > ## Linux)", "make install")));
+    }
+
+    #[test]
+    fn front_matter() {
+        let text = "---\ntitle: Notes\ntags: [a, b]\n---\n# Heading\n\nBody\n";
+        assert_eq!(
+            lines(&MarkdownCollector::default().render(text)),
+            [("L5 (# Heading)", "Heading"), ("L7 (# Heading)", "Body")]
+        );
+        let toml = "+++\ntitle = \"Notes\"\n+++\nBody\n";
+        assert_eq!(
+            lines(&MarkdownCollector::default().render(toml)),
+            [("L4", "Body")]
+        );
+        // a thematic break without a closing fence is not front matter
+        let text = "---\nBody\n";
+        assert_eq!(
+            lines(&MarkdownCollector::default().render(text)),
+            [("L2", "Body")]
+        );
+    }
+}
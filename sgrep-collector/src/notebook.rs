@@ -0,0 +1,270 @@
+use std::path::Path;
+
+use serde_json::Value;
+use tracing::instrument;
+
+use crate::html::visible_text;
+use crate::markdown::MarkdownCollector;
+use crate::{Collector, Line};
+
+/// Collects Jupyter notebooks: sources of cells as `cell7:L3` and text outputs as `cell7:out1:L2`,
+/// markdown cells are rendered to plain text and images or other binary outputs are skipped.
+#[derive(Debug, Clone, Copy)]
+pub struct NotebookCollector;
+
+impl Collector for NotebookCollector {
+    fn name(&self) -> &'static str {
+        "notebook"
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        matches!(extension, Some(e) if e == "ipynb")
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        let notebook: Value = serde_json::from_slice(&std::fs::read(path)?)?;
+        Ok(cell_lines(&notebook))
+    }
+}
+
+/// Lines of sources and outputs of all cells.
+fn cell_lines(notebook: &Value) -> Vec<Line> {
+    // cells of nbformat 3 are in the first worksheet
+    let cells = notebook
+        .get("cells")
+        .or_else(|| notebook.pointer("/worksheets/0/cells"))
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+
+    let mut lines = Vec::new();
+    for (i, cell) in cells.iter().enumerate() {
+        let prefix = format!("cell{}", i + 1);
+        let source = cell.get("source").or_else(|| cell.get("input"));
+        let source = source.map(text).unwrap_or_default();
+        match cell.get("cell_type").and_then(Value::as_str) {
+            Some("markdown") => lines.extend(
+                MarkdownCollector::default()
+                    .render(&source)
+                    .into_iter()
+                    .map(|line| Line {
+                        position: format!("{}:{}", prefix, line.position),
+                        ..line
+                    }),
+            ),
+            _ => lines.extend(numbered(&prefix, &source)),
+        }
+
+        let outputs = cell.get("outputs").and_then(Value::as_array);
+        for (j, output) in outputs.into_iter().flatten().enumerate() {
+            let prefix = format!("{}:out{}", prefix, j + 1);
+            lines.extend(output_lines(&prefix, output));
+        }
+    }
+    lines
+}
+
+/// Text of a multiline string, which is either a string or an array of lines.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn numbered(prefix: &str, text: &str) -> Vec<Line> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| Line {
+            position: format!("{}:L{}", prefix, i + 1),
+            line: line.trim_end().to_string(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Lines of stream, result, display and error outputs.
+fn output_lines(prefix: &str, output: &Value) -> Vec<Line> {
+    match output.get("output_type").and_then(Value::as_str) {
+        Some("stream") => numbered(prefix, &output.get("text").map(text).unwrap_or_default()),
+        Some("error") | Some("pyerr") => {
+            // frames of tracebacks don't end with newlines
+            let traceback = match output.get("traceback") {
+                Some(Value::Array(frames)) => frames
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                traceback => traceback.map(text).unwrap_or_default(),
+            };
+            numbered(prefix, &strip_ansi(&traceback))
+        }
+        _ => {
+            // nbformat 3 keeps data at the top level of outputs
+            let data = output.get("data").unwrap_or(output);
+            let plain = data
+                .get("text/plain")
+                .or_else(|| data.get("text"))
+                .map(text)
+                .unwrap_or_default();
+            // reprs like `<IPython.core.display.HTML object>` are placeholders of rich outputs
+            let placeholder = plain.trim().starts_with('<') && plain.trim().ends_with('>');
+            if let (true, Some(html)) = (placeholder || plain.is_empty(), data.get("text/html")) {
+                // positions of visible text may be names like `title` rather than line numbers
+                visible_text(&text(html))
+                    .into_iter()
+                    .filter(|line| !line.line.trim().is_empty())
+                    .enumerate()
+                    .map(|(i, line)| Line {
+                        position: format!("{}:L{}", prefix, i + 1),
+                        ..line
+                    })
+                    .collect()
+            } else if let (true, Some(markdown)) = (plain.is_empty(), data.get("text/markdown")) {
+                numbered(prefix, &text(markdown))
+            } else {
+                numbered(prefix, &plain)
+            }
+        }
+    }
+}
+
+/// Strips ANSI escape sequences coloring tracebacks, e.g. `\x1b[0;31m`.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // skip `[`, parameters and the final letter
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{cell_lines, output_lines, strip_ansi};
+    use crate::tests::lines;
+
+    #[test]
+    fn strip_ansi_colors() {
+        assert_eq!(
+            strip_ansi("\u{1b}[0;31mValueError\u{1b}[0m: bad \u{1b}[1;32mvalue"),
+            "ValueError: bad value"
+        );
+    }
+
+    #[test]
+    fn outputs() {
+        let stream = json!({"output_type": "stream", "text": ["a\n", "\n", "b\n"]});
+        assert_eq!(
+            lines(&output_lines("cell1:out1", &stream)),
+            [("cell1:out1:L1", "a"), ("cell1:out1:L3", "b")],
+        );
+
+        let error = json!({
+            "output_type": "error",
+            "traceback": [
+                "\u{1b}[0;31mValueError\u{1b}[0m Traceback",
+                "\u{1b}[0;31mValueError\u{1b}[0m: bad",
+            ],
+        });
+        assert_eq!(
+            lines(&output_lines("cell1:out1", &error)),
+            [
+                ("cell1:out1:L1", "ValueError Traceback"),
+                ("cell1:out1:L2", "ValueError: bad"),
+            ],
+        );
+
+        // rich outputs are collected by HTML if the plain text is a placeholder
+        let display = json!({
+            "output_type": "display_data",
+            "data": {
+                "text/plain": ["<IPython.core.display.HTML object>"],
+                "text/html": [
+                    "<html><head><title>Report</title></head>\n",
+                    "<body><table><tr><td>total</td></tr></table></body></html>",
+                ],
+            },
+        });
+        assert_eq!(
+            lines(&output_lines("cell2:out1", &display)),
+            [("cell2:out1:L1", "Report"), ("cell2:out1:L2", "total")],
+        );
+
+        let result = json!({
+            "output_type": "execute_result",
+            "data": {"text/plain": "42", "text/html": "<b>42</b>"},
+        });
+        assert_eq!(
+            lines(&output_lines("cell3:out1", &result)),
+            [("cell3:out1:L1", "42")],
+        );
+    }
+
+    #[test]
+    fn nbformat4_cells() {
+        let notebook = json!({
+            "nbformat": 4,
+            "cells": [
+                {"cell_type": "markdown", "source": ["# Title\n", "\n", "Some *text*."]},
+                {
+                    "cell_type": "code",
+                    "source": "import os\nprint(os.sep)",
+                    "outputs": [{"output_type": "stream", "text": "/\n"}],
+                },
+            ],
+        });
+        assert_eq!(
+            lines(&cell_lines(&notebook)),
+            [
+                ("cell1:L1 (# Title)", "Title"),
+                ("cell1:L3 (# Title)", "Some text."),
+                ("cell2:L1", "import os"),
+                ("cell2:L2", "print(os.sep)"),
+                ("cell2:out1:L1", "/"),
+            ],
+        );
+    }
+
+    #[test]
+    fn nbformat3_cells() {
+        let notebook = json!({
+            "nbformat": 3,
+            "worksheets": [{
+                "cells": [
+                    {"cell_type": "markdown", "source": ["Notes"]},
+                    {
+                        "cell_type": "code",
+                        "input": ["1 + 1"],
+                        "outputs": [
+                            {"output_type": "pyout", "text": ["2"]},
+                            {"output_type": "pyerr", "traceback": ["ZeroDivisionError"]},
+                        ],
+                    },
+                ],
+            }],
+        });
+        assert_eq!(
+            lines(&cell_lines(&notebook)),
+            [
+                ("cell1:L1", "Notes"),
+                ("cell2:L1", "1 + 1"),
+                ("cell2:out1:L1", "2"),
+                ("cell2:out2:L1", "ZeroDivisionError"),
+            ],
+        );
+    }
+}
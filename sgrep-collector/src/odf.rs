@@ -0,0 +1,118 @@
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::anyhow;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use tracing::instrument;
+use zip::ZipArchive;
+
+use crate::package::read_entry;
+use crate::{Collector, Line};
+
+const CONTENT: &str = "content.xml";
+const EXTENSIONS: [&str; 2] = ["odt", "odp"];
+/// Upper bound of spaces repeated by `<text:s text:c="N"/>`, which comes from the file.
+const MAX_SPACES: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct OdfCollector;
+
+impl Collector for OdfCollector {
+    fn name(&self) -> &'static str {
+        "odf"
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        extension.map_or(false, |e| EXTENSIONS.contains(&e))
+    }
+
+    fn accept_mime_type(&self, mime_type: &str) -> bool {
+        matches!(
+            mime_type,
+            "application/vnd.oasis.opendocument.text"
+                | "application/vnd.oasis.opendocument.presentation"
+        )
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        let mut archive = ZipArchive::new(File::open(path)?)?;
+        let content = read_entry(&mut archive, CONTENT)?
+            .ok_or_else(|| anyhow!("{} not found in {:?}", CONTENT, path))?;
+        collect_content(&content)
+    }
+}
+
+/// Collects `text:p` and `text:h` elements of content.xml,
+/// paragraphs of presentations are prefixed by their slide.
+fn collect_content(content: &str) -> anyhow::Result<Vec<Line>> {
+    let mut reader = Reader::from_str(content);
+    let mut buf = Vec::new();
+    let mut lines = Vec::new();
+    // paragraphs may be nested (e.g. in footnotes), so keep a stack of them
+    let mut stack: Vec<String> = Vec::new();
+    let mut slide = None;
+    let (mut paragraphs, mut headings) = (0, 0);
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) if e.name() == b"draw:page" => {
+                slide = Some(slide.unwrap_or(0) + 1);
+                paragraphs = 0;
+                headings = 0;
+            }
+            Event::Start(ref e) if e.name() == b"text:p" || e.name() == b"text:h" => {
+                stack.push(String::new())
+            }
+            Event::End(ref e) if e.name() == b"text:p" || e.name() == b"text:h" => {
+                let position = if e.name() == b"text:h" {
+                    headings += 1;
+                    format!("h{}", headings)
+                } else {
+                    paragraphs += 1;
+                    format!("p{}", paragraphs)
+                };
+                let paragraph = stack.pop().unwrap_or_default();
+                let text = paragraph.trim();
+                if !text.is_empty() {
+                    lines.push(Line {
+                        position: match slide {
+                            Some(s) => format!("slide{}:{}", s, position),
+                            None => position,
+                        },
+                        line: text.to_string(),
+                        ..Default::default()
+                    });
+                }
+            }
+            Event::Empty(ref e) => {
+                if let Some(paragraph) = stack.last_mut() {
+                    match e.name() {
+                        b"text:s" => {
+                            let count = e
+                                .attributes()
+                                .filter_map(Result::ok)
+                                .find(|attr| attr.key == b"text:c")
+                                .and_then(|attr| {
+                                    attr.unescape_and_decode_value(&reader).ok()?.parse().ok()
+                                })
+                                .unwrap_or(1);
+                            paragraph.extend(std::iter::repeat(' ').take(count.min(MAX_SPACES)));
+                        }
+                        b"text:tab" | b"text:line-break" => paragraph.push(' '),
+                        _ => (),
+                    }
+                }
+            }
+            Event::Text(ref e) => {
+                if let Some(paragraph) = stack.last_mut() {
+                    paragraph.push_str(&e.unescape_and_decode(&reader)?);
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(lines)
+}
@@ -0,0 +1,239 @@
+//! Password protected OOXML documents (MS-OFFCRYPTO), the zip package is encrypted
+//! in the `EncryptedPackage` stream of a compound file by standard or agile encryption.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::anyhow;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::crypto::{aes_decrypt, Hash, Mode};
+use crate::Encrypted;
+
+const CFB_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const ENCRYPTION_INFO: &str = "/EncryptionInfo";
+const ENCRYPTED_PACKAGE: &str = "/EncryptedPackage";
+const SEGMENT_SIZE: usize = 4096;
+/// Block keys of agile encryption to derive keys from the password hash.
+const VERIFIER_INPUT_BLOCK: [u8; 8] = [0xfe, 0xa7, 0xd2, 0x76, 0x3b, 0x4b, 0x9e, 0x79];
+const VERIFIER_VALUE_BLOCK: [u8; 8] = [0xd7, 0xaa, 0x0f, 0x6d, 0x30, 0x61, 0x34, 0x4e];
+const KEY_VALUE_BLOCK: [u8; 8] = [0x14, 0x6e, 0x0b, 0xe7, 0xab, 0xac, 0xd0, 0xd6];
+
+/// Whether the file is an encrypted OOXML package rather than a zip file.
+pub fn is_encrypted(path: &Path) -> anyhow::Result<bool> {
+    let mut signature = [0; 8];
+    if File::open(path)?.read_exact(&mut signature).is_err() || signature != CFB_SIGNATURE {
+        return Ok(false);
+    }
+    let file = cfb::open(path)?;
+    Ok(file.is_stream(ENCRYPTION_INFO) && file.is_stream(ENCRYPTED_PACKAGE))
+}
+
+/// Decrypts the zip package by the password.
+pub fn decrypt(path: &Path, password: &str) -> anyhow::Result<Vec<u8>> {
+    let mut file = cfb::open(path)?;
+    let mut info = Vec::new();
+    file.open_stream(ENCRYPTION_INFO)?.read_to_end(&mut info)?;
+    let mut package = Vec::new();
+    file.open_stream(ENCRYPTED_PACKAGE)?
+        .read_to_end(&mut package)?;
+
+    let password = password
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect::<Vec<_>>();
+    let version = (u16_at(&info, 0)?, u16_at(&info, 2)?);
+    let size = package
+        .get(..8)
+        .ok_or_else(|| anyhow!("invalid encrypted package"))?;
+    let size = u64::from_le_bytes(size.try_into()?) as usize;
+    let mut content = match version {
+        (4, 4) => {
+            let xml = info
+                .get(8..)
+                .ok_or_else(|| anyhow!("invalid encryption info"))?;
+            Agile::parse(xml)?.decrypt(&password, &package[8..])?
+        }
+        (2..=4, 2) => Standard::parse(&info)?.decrypt(&password, &package[8..])?,
+        (major, minor) => return Err(anyhow!("unsupported encryption {}.{}", major, minor)),
+    };
+    content.truncate(size);
+    Ok(content)
+}
+
+/// Hashes the password with salt iteratively.
+fn password_hash(hash: Hash, salt: &[u8], password: &[u8], spin_count: u32) -> Vec<u8> {
+    let mut h = hash.digest(&[salt, password]);
+    for i in 0..spin_count {
+        h = hash.digest(&[&i.to_le_bytes(), &h]);
+    }
+    h
+}
+
+/// Truncates or pads the bytes with 0x36 to the length.
+fn fit(mut bytes: Vec<u8>, len: usize) -> Vec<u8> {
+    bytes.resize(len, 0x36);
+    bytes
+}
+
+/// Parameters of agile encryption in the XML descriptor of EncryptionInfo.
+#[derive(Default)]
+struct Agile {
+    /// Salt, block size and hash of the key data
+    salt: Vec<u8>,
+    block_size: usize,
+    hash: Option<Hash>,
+    /// Parameters of the password key encryptor
+    key_bits: usize,
+    key_hash: Option<Hash>,
+    key_salt: Vec<u8>,
+    spin_count: u32,
+    verifier_input: Vec<u8>,
+    verifier_value: Vec<u8>,
+    key_value: Vec<u8>,
+}
+
+impl Agile {
+    fn parse(xml: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = Reader::from_reader(xml);
+        let mut buf = Vec::new();
+        let mut agile = Agile::default();
+        loop {
+            match reader.read_event(&mut buf)? {
+                Event::Start(ref e) | Event::Empty(ref e)
+                    if matches!(e.local_name(), b"keyData" | b"encryptedKey") =>
+                {
+                    let key_data = e.local_name() == b"keyData";
+                    for attr in e.attributes() {
+                        let attr = attr?;
+                        let value = attr.unescape_and_decode_value(&reader)?;
+                        match (key_data, attr.key) {
+                            (true, b"saltValue") => agile.salt = base64::decode(&value)?,
+                            (true, b"blockSize") => agile.block_size = value.parse()?,
+                            (true, b"hashAlgorithm") => agile.hash = Some(Hash::from_name(&value)?),
+                            (false, b"keyBits") => agile.key_bits = value.parse()?,
+                            (false, b"hashAlgorithm") => {
+                                agile.key_hash = Some(Hash::from_name(&value)?)
+                            }
+                            (false, b"saltValue") => agile.key_salt = base64::decode(&value)?,
+                            (false, b"spinCount") => agile.spin_count = value.parse()?,
+                            (false, b"encryptedVerifierHashInput") => {
+                                agile.verifier_input = base64::decode(&value)?
+                            }
+                            (false, b"encryptedVerifierHashValue") => {
+                                agile.verifier_value = base64::decode(&value)?
+                            }
+                            (false, b"encryptedKeyValue") => {
+                                agile.key_value = base64::decode(&value)?
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+            buf.clear();
+        }
+        Ok(agile)
+    }
+
+    fn decrypt(&self, password: &[u8], package: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let hash = self.hash.ok_or_else(|| anyhow!("missing hash algorithm"))?;
+        let key_hash = self.key_hash.unwrap_or(hash);
+        let h = password_hash(key_hash, &self.key_salt, password, self.spin_count);
+        let key_len = self.key_bits / 8;
+        let decrypt_key = |block: &[u8], data: &[u8]| {
+            let key = fit(key_hash.digest(&[&h, block]), key_len);
+            aes_decrypt(Mode::Cbc, &key, &self.key_salt, data)
+        };
+
+        let input = decrypt_key(&VERIFIER_INPUT_BLOCK, &self.verifier_input)?;
+        let value = decrypt_key(&VERIFIER_VALUE_BLOCK, &self.verifier_value)?;
+        let input = &input[..self.key_salt.len().min(input.len())];
+        let expected = key_hash.digest(&[input]);
+        if value.get(..expected.len()) != Some(&expected[..]) {
+            return Err(Encrypted::WrongPassword.into());
+        }
+        let mut key = decrypt_key(&KEY_VALUE_BLOCK, &self.key_value)?;
+        key.truncate(key_len);
+
+        let mut content = Vec::with_capacity(package.len());
+        for (i, segment) in package.chunks(SEGMENT_SIZE).enumerate() {
+            let iv = fit(
+                hash.digest(&[&self.salt, &(i as u32).to_le_bytes()]),
+                self.block_size,
+            );
+            // the package is padded to blocks, ignore the trailing garbage if any
+            let len = segment.len() / self.block_size.max(1) * self.block_size;
+            content.extend(aes_decrypt(Mode::Cbc, &key, &iv, &segment[..len])?);
+        }
+        Ok(content)
+    }
+}
+
+/// Parameters of standard encryption (AES-ECB with SHA-1).
+struct Standard {
+    key_size: usize,
+    salt: Vec<u8>,
+    verifier: Vec<u8>,
+    verifier_hash: Vec<u8>,
+}
+
+impl Standard {
+    fn parse(info: &[u8]) -> anyhow::Result<Self> {
+        let header_size = u32_at(info, 8)? as usize;
+        let header = 12;
+        let key_size = u32_at(info, header + 16)? as usize;
+        let verifier = header + header_size;
+        let salt_size = u32_at(info, verifier)? as usize;
+        let bytes = |start: usize, len: usize| {
+            info.get(start..start + len)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| anyhow!("invalid encryption verifier"))
+        };
+        Ok(Self {
+            key_size: if key_size == 0 { 128 } else { key_size },
+            salt: bytes(verifier + 4, salt_size)?,
+            verifier: bytes(verifier + 4 + salt_size, 16)?,
+            verifier_hash: bytes(verifier + 4 + salt_size + 16 + 4, 32)?,
+        })
+    }
+
+    fn decrypt(&self, password: &[u8], package: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let h = password_hash(Hash::Sha1, &self.salt, password, 50000);
+        let h = Hash::Sha1.digest(&[&h, &0u32.to_le_bytes()]);
+        let derive = |byte: u8| {
+            let mut buf = vec![byte; 64];
+            buf.iter_mut().zip(&h).for_each(|(b, h)| *b ^= h);
+            Hash::Sha1.digest(&[&buf])
+        };
+        let mut key = derive(0x36);
+        key.extend(derive(0x5c));
+        key.truncate(self.key_size / 8);
+
+        let verifier = aes_decrypt(Mode::Ecb, &key, &[], &self.verifier)?;
+        let verifier_hash = aes_decrypt(Mode::Ecb, &key, &[], &self.verifier_hash)?;
+        if Hash::Sha1.digest(&[&verifier])[..] != verifier_hash[..20] {
+            return Err(Encrypted::WrongPassword.into());
+        }
+        let len = package.len() / 16 * 16;
+        aes_decrypt(Mode::Ecb, &key, &[], &package[..len])
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> anyhow::Result<u16> {
+    let bytes = bytes
+        .get(offset..offset + 2)
+        .ok_or_else(|| anyhow!("unexpected end at {}", offset))?;
+    Ok(u16::from_le_bytes(bytes.try_into()?))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let bytes = bytes
+        .get(offset..offset + 4)
+        .ok_or_else(|| anyhow!("unexpected end at {}", offset))?;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
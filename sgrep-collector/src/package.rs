@@ -0,0 +1,119 @@
+//! Helpers for zip-based document packages (OOXML, ODF, EPUB).
+
+use std::io::{Read, Seek};
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// Reads an entry of the package as a string, returns `None` if it doesn't exist.
+pub fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> anyhow::Result<Option<String>> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut content = String::with_capacity(entry.size() as usize);
+    entry.read_to_string(&mut content)?;
+    Ok(Some(content))
+}
+
+/// Resolves a relative `target` against the directory of `base` part,
+/// e.g. (`ppt/slides/slide1.xml`, `../notesSlides/notesSlide1.xml`) => `ppt/notesSlides/notesSlide1.xml`.
+pub fn resolve(base: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return normalize(absolute.split('/'));
+    }
+    let mut segments = base.split('/').collect::<Vec<_>>();
+    segments.pop();
+    segments.extend(target.split('/'));
+    normalize(segments.into_iter())
+}
+
+fn normalize<'a>(segments: impl Iterator<Item = &'a str>) -> String {
+    let mut normalized = Vec::new();
+    for s in segments {
+        match s {
+            "" | "." => (),
+            ".." => {
+                normalized.pop();
+            }
+            s => normalized.push(s),
+        }
+    }
+    normalized.join("/")
+}
+
+#[derive(Debug, Clone)]
+pub struct Relationship {
+    pub id: String,
+    pub kind: String,
+    pub target: String,
+}
+
+/// Reads the OPC relationships of `part`, targets are resolved to absolute part names.
+pub fn relationships<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    part: &str,
+) -> anyhow::Result<Vec<Relationship>> {
+    let rels_name = match part.rsplit_once('/') {
+        Some((dir, file)) => format!("{}/_rels/{}.rels", dir, file),
+        None => format!("_rels/{}.rels", part),
+    };
+    let content = match read_entry(archive, &rels_name)? {
+        Some(content) => content,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut reader = Reader::from_str(&content);
+    let mut buf = Vec::new();
+    let mut relationships = Vec::new();
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) | Event::Empty(ref e) if e.local_name() == b"Relationship" => {
+                let mut rel = Relationship {
+                    id: String::new(),
+                    kind: String::new(),
+                    target: String::new(),
+                };
+                let mut external = false;
+                for attr in e.attributes() {
+                    let attr = attr?;
+                    let value = attr.unescape_and_decode_value(&reader)?;
+                    match attr.key {
+                        b"Id" => rel.id = value,
+                        b"Type" => rel.kind = value,
+                        b"Target" => rel.target = value,
+                        b"TargetMode" => external = value == "External",
+                        _ => (),
+                    }
+                }
+                if !external {
+                    rel.target = resolve(part, &rel.target);
+                }
+                relationships.push(rel);
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(relationships)
+}
+
+/// A package of the entries in memory.
+#[cfg(test)]
+pub fn archive(entries: &[(&str, &str)]) -> ZipArchive<std::io::Cursor<Vec<u8>>> {
+    use std::io::Write;
+
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, content) in entries {
+        writer.start_file(*name, Default::default()).unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+    }
+    ZipArchive::new(writer.finish().unwrap()).unwrap()
+}
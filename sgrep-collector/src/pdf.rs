@@ -1,10 +1,27 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 
-use lopdf::Document;
+use encoding_rs::{UTF_16BE, WINDOWS_1252};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId};
 use rayon::prelude::*;
-use tracing::instrument;
+use tracing::{debug, instrument};
 
-use crate::{Collector, Line};
+use crate::cmap::CMap;
+use crate::{pdf_crypto, Collector, Encrypted, Line};
+
+/// Entries of the document information dictionary and their positions.
+const INFO: [(&[u8], &str); 5] = [
+    (b"Title", "title"),
+    (b"Author", "author"),
+    (b"Subject", "subject"),
+    (b"Keywords", "keywords"),
+    (b"CreationDate", "created"),
+];
+/// Limit of nesting levels of outlines and name trees, in case of malformed documents.
+const MAX_DEPTH: usize = 32;
+/// Adjustments of `TJ` arrays wider than this (in thousandths of an em) separate words.
+const WORD_GAP: f64 = 200.0;
 
 #[derive(Debug, Clone, Copy)]
 pub struct PDFCollector;
@@ -18,26 +35,766 @@ impl Collector for PDFCollector {
         matches!(extension, Some(e) if e == "pdf")
     }
 
+    fn accept_mime_type(&self, mime_type: &str) -> bool {
+        mime_type == "application/pdf"
+    }
+
+    /// Collects the document information (`title`, `author`, ...), bookmarks (`outline:p12`)
+    /// and visual lines of pages in page order located by the nearest bookmark,
+    /// e.g. `p12:L3 (3.2 Results)`.
     #[instrument]
     fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
         let doc = Document::load(path)?;
-        let mut indexed_pages = doc
-            .get_pages()
-            .into_iter()
-            .par_bridge()
-            .map(|(p, _)| {
-                let mut page = doc.extract_text(&[p])?;
-                page.remove_matches("?Identity-H Unimplemented?");
-                Ok((p, page))
-            })
-            .collect::<anyhow::Result<Vec<_>>>()?;
-        indexed_pages.sort_by(|(p1, _), (p2, _)| p1.cmp(p2));
-        Ok(indexed_pages
-            .into_iter()
-            .map(|(p, page)| Line {
-                position: format!("p{}", p),
-                line: page,
+        if !pdf_crypto::is_encrypted(&doc) {
+            return collect_document(&doc);
+        }
+        // documents restricting only permissions are encrypted by the empty user password
+        match pdf_crypto::decrypt(path, "") {
+            Ok(doc) => collect_document(&doc),
+            Err(err) if err.downcast_ref() == Some(&Encrypted::WrongPassword) => {
+                Err(Encrypted::NoPassword.into())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    #[instrument(skip(password))]
+    fn collect_encrypted(&self, path: &Path, password: &str) -> anyhow::Result<Vec<Line>> {
+        collect_document(&pdf_crypto::decrypt(path, password)?)
+    }
+}
+
+fn collect_document(doc: &Document) -> anyhow::Result<Vec<Line>> {
+    let pages = doc.get_pages();
+    let mut lines = info(doc);
+
+    let bookmarks = outline(doc, &pages);
+    // pages are located by the nearest bookmark pointing at or before them
+    let mut sections = BTreeMap::new();
+    for bookmark in &bookmarks {
+        lines.push(Line {
+            position: match bookmark.page {
+                Some(p) => format!("outline:p{}", p),
+                None => "outline".to_string(),
+            },
+            line: bookmark.title.clone(),
+            ..Default::default()
+        });
+        if let Some(p) = bookmark.page {
+            sections.insert(p, bookmark.title.as_str());
+        }
+    }
+
+    let mut indexed_pages = pages
+        .iter()
+        .par_bridge()
+        .map(|(&p, &id)| Ok((p, page_lines(doc, id)?)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    indexed_pages.sort_by(|(p1, _), (p2, _)| p1.cmp(p2));
+    for (p, page) in indexed_pages {
+        let section = sections.range(..=p).next_back().map(|(_, title)| title);
+        lines.extend(page.into_iter().enumerate().map(|(i, line)| Line {
+            position: match section {
+                Some(title) => format!("p{}:L{} ({})", p, i + 1, title),
+                None => format!("p{}:L{}", p, i + 1),
+            },
+            line,
+            ..Default::default()
+        }));
+    }
+    Ok(lines)
+}
+
+/// Non-empty entries of the document information dictionary.
+fn info(doc: &Document) -> Vec<Line> {
+    let info = match doc
+        .trailer
+        .get(b"Info")
+        .and_then(|o| doc.dereference(o))
+        .and_then(|(_, o)| o.as_dict())
+    {
+        Ok(info) => info,
+        Err(_) => return Vec::new(),
+    };
+    INFO.iter()
+        .filter_map(|&(key, position)| {
+            let value = text_string(get(doc, info, key)?.as_str().ok()?);
+            let value = match key {
+                b"CreationDate" => date(&value),
+                _ => value.trim().to_string(),
+            };
+            (!value.is_empty()).then(|| Line {
+                position: position.to_string(),
+                line: value,
+                ..Default::default()
             })
-            .collect())
+        })
+        .collect()
+}
+
+/// Formats a PDF date like `D:20210102030405+08'00'` as `2021-01-02 03:04:05`.
+fn date(value: &str) -> String {
+    let digits = value.trim_start_matches("D:");
+    let part = |range: std::ops::Range<usize>| {
+        digits
+            .get(range)
+            .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))
+    };
+    match (part(0..4), part(4..6), part(6..8)) {
+        (Some(y), Some(m), Some(d)) => match (part(8..10), part(10..12), part(12..14)) {
+            (Some(h), Some(min), s) => {
+                format!("{}-{}-{} {}:{}:{}", y, m, d, h, min, s.unwrap_or("00"))
+            }
+            _ => format!("{}-{}-{}", y, m, d),
+        },
+        _ => value.to_string(),
+    }
+}
+
+struct Bookmark {
+    title: String,
+    page: Option<u32>,
+}
+
+/// Bookmarks of the document outline in document order.
+fn outline(doc: &Document, pages: &BTreeMap<u32, ObjectId>) -> Vec<Bookmark> {
+    let catalog = match doc.catalog() {
+        Ok(catalog) => catalog,
+        Err(_) => return Vec::new(),
+    };
+    let numbers = pages
+        .iter()
+        .map(|(&p, &id)| (id, p))
+        .collect::<HashMap<_, _>>();
+    let mut bookmarks = Vec::new();
+    let mut visited = HashSet::new();
+    let first = get(doc, catalog, b"Outlines")
+        .and_then(|o| o.as_dict().ok())
+        .and_then(|outlines| outlines.get(b"First").ok());
+    let mut stack = first.map(|first| vec![(first, 0)]).unwrap_or_default();
+    while let Some((item, depth)) = stack.pop() {
+        // items are linked by references, stop at cycles
+        if let Ok(id) = item.as_reference() {
+            if !visited.insert(id) {
+                continue;
+            }
+        }
+        let dict = match doc.dereference(item).and_then(|(_, o)| o.as_dict()) {
+            Ok(dict) => dict,
+            Err(_) => continue,
+        };
+        if let Some(title) = get(doc, dict, b"Title").and_then(|o| o.as_str().ok()) {
+            let dest = get(doc, dict, b"Dest").or_else(|| {
+                let action = get(doc, dict, b"A")?.as_dict().ok()?;
+                get(doc, action, b"D")
+            });
+            bookmarks.push(Bookmark {
+                title: text_string(title).trim().to_string(),
+                page: dest.and_then(|d| destination_page(doc, catalog, d, &numbers)),
+            });
+        }
+        // visit children before the next sibling
+        if let Ok(next) = dict.get(b"Next") {
+            stack.push((next, depth));
+        }
+        if depth < MAX_DEPTH {
+            if let Ok(first) = dict.get(b"First") {
+                stack.push((first, depth + 1));
+            }
+        }
+    }
+    bookmarks
+}
+
+/// Page number of an explicit destination `[page /XYZ ...]` or a named one.
+fn destination_page(
+    doc: &Document,
+    catalog: &Dictionary,
+    dest: &Object,
+    numbers: &HashMap<ObjectId, u32>,
+) -> Option<u32> {
+    let dest = match dest {
+        Object::Name(name) | Object::String(name, _) => named_destination(doc, catalog, name)?,
+        dest => dest,
+    };
+    let dest = match dest {
+        Object::Dictionary(dict) => get(doc, dict, b"D")?,
+        dest => dest,
+    };
+    let page = dest.as_array().ok()?.first()?.as_reference().ok()?;
+    numbers.get(&page).copied()
+}
+
+/// Looks up a named destination in the `Dests` name tree or the legacy `Dests` dictionary.
+fn named_destination<'a>(
+    doc: &'a Document,
+    catalog: &'a Dictionary,
+    name: &[u8],
+) -> Option<&'a Object> {
+    if let Some(tree) = get(doc, catalog, b"Names")
+        .and_then(|o| o.as_dict().ok())
+        .and_then(|names| get(doc, names, b"Dests"))
+        .and_then(|o| o.as_dict().ok())
+    {
+        if let Some(dest) = name_tree_lookup(doc, tree, name, 0) {
+            return Some(dest);
+        }
+    }
+    get(doc, get(doc, catalog, b"Dests")?.as_dict().ok()?, name)
+}
+
+fn name_tree_lookup<'a>(
+    doc: &'a Document,
+    node: &'a Dictionary,
+    name: &[u8],
+    depth: usize,
+) -> Option<&'a Object> {
+    if let Some(names) = get(doc, node, b"Names").and_then(|o| o.as_array().ok()) {
+        for pair in names.chunks_exact(2) {
+            let key = doc
+                .dereference(&pair[0])
+                .ok()
+                .and_then(|(_, key)| key.as_str().ok());
+            if key == Some(name) {
+                return doc.dereference(&pair[1]).ok().map(|(_, o)| o);
+            }
+        }
+    }
+    if depth >= MAX_DEPTH {
+        return None;
+    }
+    get(doc, node, b"Kids")?
+        .as_array()
+        .ok()?
+        .iter()
+        .filter_map(|kid| doc.dereference(kid).ok()?.1.as_dict().ok())
+        .find_map(|kid| name_tree_lookup(doc, kid, name, depth + 1))
+}
+
+/// Gets the value of key in a dictionary, following references.
+fn get<'a>(doc: &'a Document, dict: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+    let value = dict.get(key).ok()?;
+    doc.dereference(value).ok().map(|(_, o)| o)
+}
+
+/// Decodes a PDF text string, UTF-16BE with BOM or PDFDocEncoding (mostly Windows-1252).
+fn text_string(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFE, 0xFF, rest @ ..] => UTF_16BE.decode_without_bom_handling(rest).0.into_owned(),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        bytes => WINDOWS_1252
+            .decode_without_bom_handling(bytes)
+            .0
+            .into_owned(),
+    }
+}
+
+/// Decoder of strings shown by a font.
+struct Font<'a> {
+    encoding: &'a str,
+    /// Texts of codes replaced by `Differences` of the encoding dictionary.
+    differences: HashMap<u8, String>,
+    /// Composite (Type0) fonts use multi-byte codes.
+    composite: bool,
+    to_unicode: Option<CMap>,
+}
+
+impl<'a> Font<'a> {
+    fn new(doc: &Document, font: &'a Dictionary) -> Self {
+        let to_unicode = font
+            .get(b"ToUnicode")
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_stream())
+            .ok()
+            .map(|stream| {
+                let content = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+                CMap::parse(&content)
+            });
+        let differences = font
+            .get(b"Encoding")
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_dict())
+            .and_then(|encoding| encoding.get(b"Differences"))
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_array())
+            .map_or_else(|_| HashMap::new(), |array| differences(array));
+        let encoding = font.get_font_encoding();
+        if to_unicode.is_none() && encoding.starts_with("Identity-") {
+            // CIDs can't be mapped to Unicode without ToUnicode
+            debug!("no ToUnicode CMap for {} font", encoding);
+        }
+        Self {
+            encoding,
+            differences,
+            composite: font.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Type0"[..]),
+            to_unicode,
+        }
+    }
+
+    /// Decodes by the ToUnicode CMap, codes it doesn't map fall back to the encoding.
+    fn decode(&self, bytes: &[u8]) -> String {
+        match self.to_unicode {
+            Some(ref cmap) => {
+                let len = if self.composite { 2 } else { 1 };
+                cmap.decode_or(bytes, len, |code| self.decode_codes(code))
+            }
+            None => self.decode_codes(bytes),
+        }
+    }
+
+    fn decode_codes(&self, bytes: &[u8]) -> String {
+        match self.encoding {
+            // predefined Unicode CMaps like UniGB-UCS2-H and UniJIS-UTF16-H
+            e if e.contains("UCS2") || e.contains("UTF16") => {
+                UTF_16BE.decode_without_bom_handling(bytes).0.into_owned()
+            }
+            "Identity-H" | "Identity-V" => String::new(),
+            e if self.composite || self.differences.is_empty() => {
+                Document::decode_text(Some(e), bytes)
+            }
+            e => bytes
+                .iter()
+                .map(|b| match self.differences.get(b) {
+                    Some(text) => text.clone(),
+                    None => Document::decode_text(Some(e), &[*b]),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Texts of codes in a `Differences` array, e.g. `[39 /quoteright 96 /quoteleft]`,
+/// glyphs without known names are left to the base encoding.
+fn differences(array: &[Object]) -> HashMap<u8, String> {
+    let mut texts = HashMap::new();
+    let mut code = 0;
+    for object in array {
+        match object {
+            Object::Integer(i) => code = *i,
+            Object::Name(name) => {
+                if let (Ok(c), Some(text)) = (u8::try_from(code), glyph_text(name)) {
+                    texts.insert(c, text);
+                }
+                code += 1;
+            }
+            _ => (),
+        }
+    }
+    texts
+}
+
+/// Common names of the Adobe Glyph List other than letters and digits.
+const GLYPHS: [(&str, &str); 48] = [
+    ("space", " "),
+    ("exclam", "!"),
+    ("quotedbl", "\""),
+    ("numbersign", "#"),
+    ("dollar", "$"),
+    ("percent", "%"),
+    ("ampersand", "&"),
+    ("quotesingle", "'"),
+    ("parenleft", "("),
+    ("parenright", ")"),
+    ("asterisk", "*"),
+    ("plus", "+"),
+    ("comma", ","),
+    ("hyphen", "-"),
+    ("period", "."),
+    ("slash", "/"),
+    ("zero", "0"),
+    ("one", "1"),
+    ("two", "2"),
+    ("three", "3"),
+    ("four", "4"),
+    ("five", "5"),
+    ("six", "6"),
+    ("seven", "7"),
+    ("eight", "8"),
+    ("nine", "9"),
+    ("colon", ":"),
+    ("semicolon", ";"),
+    ("less", "<"),
+    ("equal", "="),
+    ("greater", ">"),
+    ("question", "?"),
+    ("at", "@"),
+    ("bracketleft", "["),
+    ("bracketright", "]"),
+    ("underscore", "_"),
+    ("quoteleft", "\u{2018}"),
+    ("quoteright", "\u{2019}"),
+    ("quotedblleft", "\u{201C}"),
+    ("quotedblright", "\u{201D}"),
+    ("endash", "\u{2013}"),
+    ("emdash", "\u{2014}"),
+    ("bullet", "\u{2022}"),
+    ("ellipsis", "\u{2026}"),
+    ("fi", "fi"),
+    ("fl", "fl"),
+    ("ff", "ff"),
+    ("ffi", "ffi"),
+];
+
+/// Text of a glyph name, e.g. `A`, `quoteright`, `uni2019` or `u1D400`,
+/// suffixes of variants like `a.sc` are ignored.
+fn glyph_text(name: &[u8]) -> Option<String> {
+    let name = std::str::from_utf8(name).ok()?;
+    let name = name.split('.').next().unwrap_or_default();
+    let hex = |digits: &str| {
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+    };
+    if let Some(&(_, text)) = GLYPHS.iter().find(|(glyph, _)| *glyph == name) {
+        return Some(text.to_string());
+    }
+    // sequences of 4-digit codes, e.g. uni00660069
+    if let Some(digits) = name.strip_prefix("uni") {
+        if !digits.is_empty() && digits.len() % 4 == 0 {
+            return (0..digits.len())
+                .step_by(4)
+                .map(|i| hex(digits.get(i..i + 4)?))
+                .collect();
+        }
+    }
+    match name.strip_prefix('u') {
+        Some(digits) if (4..=6).contains(&digits.len()) => hex(digits).map(String::from),
+        _ if name.len() == 1 && name.bytes().all(|b| b.is_ascii_alphabetic()) => {
+            Some(name.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Text of a page broken into visual lines, a line breaks once the text moves vertically
+/// on the page by more than half of the font size.
+struct PageText<'a> {
+    fonts: BTreeMap<Vec<u8>, Font<'a>>,
+    font: Vec<u8>,
+    font_size: f64,
+    /// Horizontal and vertical scales of the text matrix.
+    scale: (f64, f64),
+    leading: f64,
+    /// Current transformation matrix from user space to the page, changed by `cm`.
+    ctm: [f64; 6],
+    /// Matrices saved by `q` and restored by `Q`.
+    saved: Vec<[f64; 6]>,
+    /// Origin of the text line matrix in user space, reset by `BT`.
+    origin: (f64, f64),
+    /// Last position text moved to on the page.
+    position: (f64, f64),
+    /// Estimated end of the text shown on the current line on the page.
+    end: f64,
+    line: String,
+    lines: Vec<String>,
+}
+
+impl<'a> PageText<'a> {
+    fn new(fonts: BTreeMap<Vec<u8>, Font<'a>>) -> Self {
+        Self {
+            fonts,
+            font: Vec::new(),
+            font_size: 0.0,
+            scale: (1.0, 1.0),
+            leading: 0.0,
+            ctm: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+            saved: Vec::new(),
+            origin: (0.0, 0.0),
+            position: (0.0, 0.0),
+            end: 0.0,
+            line: String::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Font size on the page.
+    fn size(&self) -> f64 {
+        let [_, _, c, d, _, _] = self.ctm;
+        (self.font_size * self.scale.1 * c.hypot(d)).abs().max(1.0)
+    }
+
+    /// Position on the page of a point in user space.
+    fn transform(&self, x: f64, y: f64) -> (f64, f64) {
+        let [a, b, c, d, e, f] = self.ctm;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    fn apply(&mut self, operator: &str, operands: &[Object]) {
+        match (operator, operands) {
+            ("Tf", [font, size, ..]) => {
+                self.font = font.as_name().map(<[u8]>::to_vec).unwrap_or_default();
+                self.font_size = number(size).unwrap_or_default();
+            }
+            ("q", _) => self.saved.push(self.ctm),
+            ("Q", _) => {
+                if let Some(ctm) = self.saved.pop() {
+                    self.ctm = ctm;
+                }
+            }
+            ("cm", [a, b, c, d, e, f]) => {
+                let [a, b, c, d, e, f] = [a, b, c, d, e, f].map(|n| number(n).unwrap_or_default());
+                let [a0, b0, c0, d0, e0, f0] = self.ctm;
+                self.ctm = [
+                    a * a0 + b * c0,
+                    a * b0 + b * d0,
+                    c * a0 + d * c0,
+                    c * b0 + d * d0,
+                    e * a0 + f * c0 + e0,
+                    e * b0 + f * d0 + f0,
+                ];
+            }
+            ("BT", _) => {
+                self.scale = (1.0, 1.0);
+                self.origin = (0.0, 0.0);
+            }
+            ("TL", [leading]) => self.leading = number(leading).unwrap_or_default(),
+            ("Tm", [a, _, _, d, e, f]) => {
+                self.scale = (number(a).unwrap_or(1.0), number(d).unwrap_or(1.0));
+                self.move_to(number(e).unwrap_or_default(), number(f).unwrap_or_default());
+            }
+            ("Td" | "TD", [tx, ty]) => {
+                let (tx, ty) = (
+                    number(tx).unwrap_or_default(),
+                    number(ty).unwrap_or_default(),
+                );
+                if operator == "TD" {
+                    self.leading = -ty;
+                }
+                self.move_to(
+                    self.origin.0 + tx * self.scale.0,
+                    self.origin.1 + ty * self.scale.1,
+                );
+            }
+            ("T*" | "'" | "\"", _) => {
+                self.move_to(self.origin.0, self.origin.1 - self.leading * self.scale.1)
+            }
+            _ => (),
+        }
+        if matches!(operator, "Tj" | "TJ" | "'" | "\"") {
+            let shown = self.show(operands);
+            let [a, b, _, _, _, _] = self.ctm;
+            self.end += shown as f64 * self.font_size * (self.scale.0 * a.hypot(b)).abs() / 2.0;
+        }
+    }
+
+    /// Moves the text line to a point in user space.
+    fn move_to(&mut self, x: f64, y: f64) {
+        let (page_x, page_y) = self.transform(x, y);
+        if (page_y - self.position.1).abs() > self.size() / 2.0 {
+            self.break_line();
+        } else if page_x > self.end + self.size() / 4.0 && !self.line.ends_with(' ') {
+            self.line.push(' ');
+        }
+        self.origin = (x, y);
+        self.position = (page_x, page_y);
+        self.end = page_x;
+    }
+
+    /// Appends text of the shown strings, returns the count of characters.
+    fn show(&mut self, operands: &[Object]) -> usize {
+        let mut count = 0;
+        for operand in operands {
+            match operand {
+                Object::String(bytes, _) => {
+                    let text = match self.fonts.get(&self.font) {
+                        Some(font) => font.decode(bytes),
+                        None => Document::decode_text(None, bytes),
+                    };
+                    count += text.chars().count();
+                    self.line.push_str(&text);
+                }
+                Object::Array(items) => count += self.show(items),
+                adjustment => {
+                    let gap = number(adjustment).map_or(false, |n| -n > WORD_GAP);
+                    if gap && !self.line.ends_with(' ') {
+                        self.line.push(' ');
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    fn break_line(&mut self) {
+        let text = self.line.trim();
+        if !text.is_empty() {
+            self.lines.push(text.to_string());
+        }
+        self.line.clear();
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.break_line();
+        self.lines
+    }
+}
+
+/// Non-empty visual lines of a page.
+fn page_lines(doc: &Document, page_id: ObjectId) -> anyhow::Result<Vec<String>> {
+    let fonts = doc
+        .get_page_fonts(page_id)
+        .into_iter()
+        .map(|(name, font)| (name, Font::new(doc, font)))
+        .collect();
+    let content = Content::decode(&doc.get_page_content(page_id)?)?;
+    let mut text = PageText::new(fonts);
+    for operation in &content.operations {
+        text.apply(&operation.operator, &operation.operands);
+    }
+    Ok(text.finish())
+}
+
+fn number(object: &Object) -> Option<f64> {
+    match *object {
+        Object::Integer(i) => Some(i as f64),
+        Object::Real(r) => Some(f64::from(r)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use lopdf::{Object, StringFormat};
+
+    use super::{date, differences, text_string, Font, PageText};
+    use crate::cmap::CMap;
+
+    fn string(text: &str) -> Object {
+        Object::String(text.as_bytes().to_vec(), StringFormat::Literal)
+    }
+
+    fn lines(operations: Vec<(&str, Vec<Object>)>) -> Vec<String> {
+        let mut text = PageText::new(BTreeMap::new());
+        for (operator, operands) in operations {
+            text.apply(operator, &operands);
+        }
+        text.finish()
+    }
+
+    #[test]
+    fn break_lines_by_position() {
+        let lines = lines(vec![
+            ("BT", vec![]),
+            ("Tf", vec![Object::Name(b"F1".to_vec()), 12.into()]),
+            ("Td", vec![72.into(), 700.into()]),
+            ("Tj", vec![string("Hello")]),
+            ("Tj", vec![string(" world")]),
+            ("Td", vec![0.into(), (-14).into()]),
+            ("Tj", vec![string("Next line")]),
+            // next column on the same baseline
+            ("Td", vec![100.into(), 0.into()]),
+            ("Tj", vec![string("column")]),
+            ("ET", vec![]),
+        ]);
+        assert_eq!(lines, vec!["Hello world", "Next line column"]);
+    }
+
+    #[test]
+    fn break_lines_by_leading() {
+        let lines = lines(vec![
+            ("BT", vec![]),
+            ("Tf", vec![Object::Name(b"F1".to_vec()), 10.into()]),
+            (
+                "Tm",
+                vec![
+                    1.into(),
+                    0.into(),
+                    0.into(),
+                    1.into(),
+                    50.into(),
+                    500.into(),
+                ],
+            ),
+            (
+                "TJ",
+                vec![Object::Array(vec![
+                    string("Hel"),
+                    (-50).into(),
+                    string("lo"),
+                    (-250).into(),
+                    string("world"),
+                ])],
+            ),
+            ("TL", vec![12.into()]),
+            ("T*", vec![]),
+            ("Tj", vec![string("second")]),
+            ("'", vec![string("third")]),
+            ("ET", vec![]),
+        ]);
+        assert_eq!(lines, vec!["Hello world", "second", "third"]);
+    }
+
+    #[test]
+    fn break_lines_by_transformation() {
+        let translate = |y: i64| vec![1.into(), 0.into(), 0.into(), 1.into(), 0.into(), y.into()];
+        let lines = lines(vec![
+            ("q", vec![]),
+            ("cm", translate(-100)),
+            ("BT", vec![]),
+            ("Tf", vec![Object::Name(b"F1".to_vec()), 12.into()]),
+            ("Td", vec![72.into(), 700.into()]),
+            ("Tj", vec![string("Shifted")]),
+            ("ET", vec![]),
+            ("Q", vec![]),
+            // same text position, but 100 units higher on the page
+            ("BT", vec![]),
+            ("Td", vec![72.into(), 700.into()]),
+            ("Tj", vec![string("Top")]),
+            ("ET", vec![]),
+            ("q", vec![]),
+            (
+                "cm",
+                vec![2.into(), 0.into(), 0.into(), 2.into(), 0.into(), 0.into()],
+            ),
+            ("BT", vec![]),
+            ("Td", vec![100.into(), 350.into()]),
+            ("Tj", vec![string("Scaled")]),
+            ("ET", vec![]),
+            ("Q", vec![]),
+        ]);
+        assert_eq!(lines, vec!["Shifted", "Top Scaled"]);
+    }
+
+    #[test]
+    fn dates_and_text_strings() {
+        assert_eq!(date("D:20210102030405+08'00'"), "2021-01-02 03:04:05");
+        assert_eq!(date("D:202101021304"), "2021-01-02 13:04:00");
+        assert_eq!(date("D:20210102"), "2021-01-02");
+        assert_eq!(date("yesterday"), "yesterday");
+
+        assert_eq!(text_string(b"\xFE\xFF\x00R\x00\xE9\x00s"), "Rés");
+        assert_eq!(text_string("\u{FEFF}Rés".as_bytes()), "Rés");
+        assert_eq!(text_string(b"R\xE9s \x93quoted\x94"), "Rés “quoted”");
+    }
+
+    #[test]
+    fn decode_unmapped_codes_by_encoding() {
+        let font = |to_unicode: Option<&[u8]>| Font {
+            encoding: "WinAnsiEncoding",
+            differences: differences(&[
+                39.into(),
+                Object::Name(b"quoteright".to_vec()),
+                Object::Name(b"uni00660069".to_vec()),
+                Object::Name(b"g42".to_vec()),
+            ]),
+            composite: false,
+            to_unicode: to_unicode.map(CMap::parse),
+        };
+        // 0x41 is mapped by ToUnicode, the others fall back to differences and WinAnsiEncoding
+        let cmap = b"1 beginbfchar <41> <0391> endbfchar";
+        assert_eq!(font(Some(cmap)).decode(b"AB'(\x29"), "\u{391}B\u{2019}fi)");
+        assert_eq!(font(None).decode(b"AB'"), "AB\u{2019}");
+
+        let identity = Font {
+            encoding: "Identity-H",
+            differences: HashMap::new(),
+            composite: true,
+            to_unicode: Some(CMap::parse(b"1 beginbfchar <0024> <0041> endbfchar")),
+        };
+        assert_eq!(identity.decode(b"\x00\x24\x00\x25"), "A");
     }
 }
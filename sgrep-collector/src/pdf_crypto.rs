@@ -0,0 +1,350 @@
+//! Standard security handler of encrypted PDFs, RC4 and AES of revision 2 to 6.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::crypto::{aes128_cbc_encrypt, aes_decrypt, rc4, Hash, Mode};
+use crate::Encrypted;
+
+/// Padding of passwords in revision 2 to 4.
+const PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+/// Object streams fail to parse while encrypted, they are renamed before loading and
+/// restored after decryption, both names must be of the same length.
+const OBJECT_STREAM: &[u8] = b"/ObjStm";
+const HIDDEN_OBJECT_STREAM: &[u8] = b"/ObjStX";
+
+/// Whether the document is encrypted.
+pub fn is_encrypted(doc: &Document) -> bool {
+    doc.trailer.has(b"Encrypt")
+}
+
+/// Loads and decrypts the document by the user or owner password.
+pub fn decrypt(path: &Path, password: &str) -> anyhow::Result<Document> {
+    let mut content = fs::read(path)?;
+    replace(&mut content, OBJECT_STREAM, HIDDEN_OBJECT_STREAM);
+    let mut doc = Document::load_mem(&content)?;
+    let encrypt = match doc.trailer.get(b"Encrypt") {
+        Ok(Object::Reference(id)) => Some(*id),
+        _ => None,
+    };
+    let dict = match doc.trailer.get(b"Encrypt") {
+        Ok(encrypt) => doc.dereference(encrypt)?.1.as_dict()?,
+        Err(_) => return Ok(Document::load(path)?),
+    };
+    let id = doc
+        .trailer
+        .get(b"ID")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|ids| ids.first())
+        .and_then(|id| id.as_str().ok())
+        .unwrap_or_default();
+    let handler = SecurityHandler::new(dict, id, password.as_bytes())?;
+
+    for (&object_id, object) in doc.objects.iter_mut() {
+        if Some(object_id) == encrypt {
+            continue;
+        }
+        handler.decrypt(object_id, object)?;
+    }
+    doc.trailer.remove(b"Encrypt");
+
+    // reload the document to parse the decrypted object streams
+    let mut content = Vec::new();
+    doc.save_to(&mut content)?;
+    replace(&mut content, HIDDEN_OBJECT_STREAM, OBJECT_STREAM);
+    Ok(Document::load_mem(&content)?)
+}
+
+/// Replaces all occurrences of a pattern with another of the same length.
+fn replace(content: &mut [u8], from: &[u8], to: &[u8]) {
+    let mut i = 0;
+    while i + from.len() <= content.len() {
+        if &content[i..i + from.len()] == from {
+            content[i..i + from.len()].copy_from_slice(to);
+            i += from.len();
+        } else {
+            i += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Method {
+    Identity,
+    Rc4,
+    Aes,
+}
+
+struct SecurityHandler {
+    key: Vec<u8>,
+    revision: i64,
+    strings: Method,
+    streams: Method,
+    encrypt_metadata: bool,
+}
+
+impl SecurityHandler {
+    fn new(dict: &Dictionary, id: &[u8], password: &[u8]) -> anyhow::Result<Self> {
+        let filter = dict.get(b"Filter").and_then(Object::as_name)?;
+        if filter != b"Standard" {
+            return Err(anyhow!(
+                "unsupported security handler {}",
+                String::from_utf8_lossy(filter)
+            ));
+        }
+        let version = dict.get(b"V").and_then(Object::as_i64).unwrap_or(0);
+        let revision = dict.get(b"R").and_then(Object::as_i64)?;
+        let encrypt_metadata = !matches!(dict.get(b"EncryptMetadata"), Ok(Object::Boolean(false)));
+        let (strings, streams) = match version {
+            4 | 5 => (crypt_filter(dict, b"StrF")?, crypt_filter(dict, b"StmF")?),
+            _ => (Method::Rc4, Method::Rc4),
+        };
+        let (o, u) = (string(dict, b"O")?, string(dict, b"U")?);
+
+        let key = if revision >= 5 {
+            let password = &password[..password.len().min(127)];
+            let (oe, ue) = (string(dict, b"OE")?, string(dict, b"UE")?);
+            let u = u.get(..48).ok_or_else(|| anyhow!("invalid U entry"))?;
+            let o = o.get(..48).ok_or_else(|| anyhow!("invalid O entry"))?;
+            if hash_r6(revision, password, &u[32..40], &[])? == &u[..32] {
+                let key = hash_r6(revision, password, &u[40..48], &[])?;
+                aes_decrypt(Mode::Cbc, &key, &[0; 16], ue)?
+            } else if hash_r6(revision, password, &o[32..40], u)? == &o[..32] {
+                let key = hash_r6(revision, password, &o[40..48], u)?;
+                aes_decrypt(Mode::Cbc, &key, &[0; 16], oe)?
+            } else {
+                return Err(Encrypted::WrongPassword.into());
+            }
+        } else {
+            let length = match revision {
+                2 => 5,
+                _ => dict.get(b"Length").and_then(Object::as_i64).unwrap_or(40) as usize / 8,
+            };
+            let permissions = dict.get(b"P").and_then(Object::as_i64)? as i32;
+            let params = Params {
+                revision,
+                length: length.clamp(5, 16),
+                o,
+                u,
+                permissions,
+                id,
+                encrypt_metadata,
+            };
+            match params.user_key(password) {
+                Some(key) => key,
+                None => params
+                    .user_key(&params.owner_to_user(password))
+                    .ok_or(Encrypted::WrongPassword)?,
+            }
+        };
+        Ok(Self {
+            key,
+            revision,
+            strings,
+            streams,
+            encrypt_metadata,
+        })
+    }
+
+    /// Decrypts strings and streams of the object in place.
+    fn decrypt(&self, id: ObjectId, object: &mut Object) -> anyhow::Result<()> {
+        match object {
+            Object::String(bytes, _) => *bytes = self.decrypt_bytes(id, self.strings, bytes)?,
+            Object::Array(objects) => {
+                for object in objects {
+                    self.decrypt(id, object)?;
+                }
+            }
+            Object::Dictionary(dict) => {
+                for (_, object) in dict.iter_mut() {
+                    self.decrypt(id, object)?;
+                }
+            }
+            Object::Stream(stream) => {
+                for (_, object) in stream.dict.iter_mut() {
+                    self.decrypt(id, object)?;
+                }
+                // cross-reference streams are never encrypted
+                let plain = stream.dict.type_is(b"XRef")
+                    || (!self.encrypt_metadata && stream.dict.type_is(b"Metadata"));
+                if !plain {
+                    let content = self.decrypt_bytes(id, self.streams, &stream.content)?;
+                    stream.set_content(content);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn decrypt_bytes(
+        &self,
+        (num, gen): ObjectId,
+        method: Method,
+        bytes: &[u8],
+    ) -> anyhow::Result<Vec<u8>> {
+        if method == Method::Identity || bytes.is_empty() {
+            return Ok(bytes.to_vec());
+        }
+        let key = if self.revision >= 5 {
+            self.key.clone()
+        } else {
+            let salt: &[u8] = if method == Method::Aes { b"sAlT" } else { b"" };
+            let mut key = md5::compute(
+                [
+                    &self.key[..],
+                    &num.to_le_bytes()[..3],
+                    &gen.to_le_bytes()[..],
+                    salt,
+                ]
+                .concat(),
+            )
+            .to_vec();
+            key.truncate((self.key.len() + 5).min(16));
+            key
+        };
+        match method {
+            Method::Rc4 => Ok(rc4(&key, bytes)),
+            _ if bytes.len() < 32 || bytes.len() % 16 != 0 => {
+                Err(anyhow!("invalid AES data of object {}", num))
+            }
+            _ => aes_decrypt(Mode::CbcPadded, &key, &bytes[..16], &bytes[16..]),
+        }
+    }
+}
+
+fn string<'a>(dict: &'a Dictionary, key: &[u8]) -> anyhow::Result<&'a [u8]> {
+    Ok(dict.get(key).and_then(Object::as_str)?)
+}
+
+/// Method of the crypt filter named by `StrF` or `StmF`.
+fn crypt_filter(dict: &Dictionary, key: &[u8]) -> anyhow::Result<Method> {
+    let name = match dict.get(key).and_then(Object::as_name) {
+        Ok(name) => name,
+        Err(_) => return Ok(Method::Identity),
+    };
+    if name == b"Identity" {
+        return Ok(Method::Identity);
+    }
+    let method = dict
+        .get(b"CF")
+        .and_then(Object::as_dict)
+        .and_then(|filters| filters.get(name))
+        .and_then(Object::as_dict)
+        .and_then(|filter| filter.get(b"CFM"))
+        .and_then(Object::as_name)?;
+    match method {
+        b"None" => Ok(Method::Identity),
+        b"V2" => Ok(Method::Rc4),
+        b"AESV2" | b"AESV3" => Ok(Method::Aes),
+        _ => Err(anyhow!(
+            "unsupported crypt filter {}",
+            String::from_utf8_lossy(method)
+        )),
+    }
+}
+
+/// Parameters of the key derivation in revision 2 to 4.
+struct Params<'a> {
+    revision: i64,
+    /// Key length in bytes.
+    length: usize,
+    o: &'a [u8],
+    u: &'a [u8],
+    permissions: i32,
+    id: &'a [u8],
+    encrypt_metadata: bool,
+}
+
+impl Params<'_> {
+    /// Computes the file key by the user password (algorithm 2) and checks it against `U`.
+    fn user_key(&self, password: &[u8]) -> Option<Vec<u8>> {
+        let mut input = pad(password).to_vec();
+        input.extend_from_slice(self.o);
+        input.extend_from_slice(&self.permissions.to_le_bytes());
+        input.extend_from_slice(self.id);
+        if self.revision >= 4 && !self.encrypt_metadata {
+            input.extend_from_slice(&[0xFF; 4]);
+        }
+        let mut key = md5::compute(&input).to_vec();
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                key = md5::compute(&key[..self.length]).to_vec();
+            }
+        }
+        key.truncate(self.length);
+
+        let valid = if self.revision == 2 {
+            rc4(&key, &PADDING) == self.u
+        } else {
+            let mut hash = rc4(&key, &md5::compute([&PADDING[..], self.id].concat())[..]);
+            for i in 1..=19 {
+                hash = rc4(&xor(&key, i), &hash);
+            }
+            self.u.get(..16) == Some(&hash[..])
+        };
+        valid.then(|| key)
+    }
+
+    /// Recovers the padded user password from `O` by the owner password (algorithm 7).
+    fn owner_to_user(&self, password: &[u8]) -> Vec<u8> {
+        let mut hash = md5::compute(pad(password)).to_vec();
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                hash = md5::compute(&hash).to_vec();
+            }
+        }
+        let key = &hash[..self.length];
+        if self.revision == 2 {
+            rc4(key, self.o)
+        } else {
+            (0..=19)
+                .rev()
+                .fold(self.o.to_vec(), |user, i| rc4(&xor(key, i), &user))
+        }
+    }
+}
+
+fn pad(password: &[u8]) -> [u8; 32] {
+    let mut padded = PADDING;
+    let len = password.len().min(32);
+    padded[..len].copy_from_slice(&password[..len]);
+    padded[len..].copy_from_slice(&PADDING[..32 - len]);
+    padded
+}
+
+fn xor(key: &[u8], byte: u8) -> Vec<u8> {
+    key.iter().map(|b| b ^ byte).collect()
+}
+
+/// Password hash of revision 5 (SHA-256) and 6 (algorithm 2.B).
+fn hash_r6(revision: i64, password: &[u8], salt: &[u8], user: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut k = Hash::Sha256.digest(&[password, salt, user]);
+    if revision == 5 {
+        return Ok(k);
+    }
+    let mut round = 0;
+    loop {
+        let k1 = [password, &k[..], user].concat().repeat(64);
+        let e = aes128_cbc_encrypt(&k[..16], &k[16..32], &k1)?;
+        let hash = match e[..16].iter().map(|&b| b as usize).sum::<usize>() % 3 {
+            0 => Hash::Sha256,
+            1 => Hash::Sha384,
+            _ => Hash::Sha512,
+        };
+        k = hash.digest(&[&e]);
+        round += 1;
+        if round >= 64 && e.last().map_or(0, |&b| b as usize) <= round - 32 {
+            break;
+        }
+    }
+    k.truncate(32);
+    Ok(k)
+}
@@ -0,0 +1,193 @@
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use tracing::{debug, instrument};
+use zip::ZipArchive;
+
+use crate::package::{read_entry, relationships};
+use crate::{Collector, Line};
+
+const PRESENTATION: &str = "ppt/presentation.xml";
+const SLIDE_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide";
+const NOTES_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/notesSlide";
+
+#[derive(Debug, Clone, Copy)]
+pub struct PptxCollector;
+
+impl Collector for PptxCollector {
+    fn name(&self) -> &'static str {
+        "pptx"
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        matches!(extension, Some(e) if e == "pptx")
+    }
+
+    fn accept_mime_type(&self, mime_type: &str) -> bool {
+        mime_type == "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        slide_lines(&mut ZipArchive::new(File::open(path)?)?)
+    }
+}
+
+/// Paragraphs of slides as `slide3` and of their speaker notes as `slide3:notes`.
+fn slide_lines<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<Vec<Line>> {
+    let mut lines = Vec::new();
+    for (i, slide) in slides(archive)?.into_iter().enumerate() {
+        let position = format!("slide{}", i + 1);
+        debug!("collect {} from {}", position, slide);
+        if let Some(content) = read_entry(archive, &slide)? {
+            lines.extend(paragraphs(&content)?.into_iter().map(|line| Line {
+                position: position.clone(),
+                line,
+                ..Default::default()
+            }));
+        }
+
+        let notes = relationships(archive, &slide)?
+            .into_iter()
+            .find(|rel| rel.kind == NOTES_RELATIONSHIP);
+        if let Some(notes) = notes {
+            if let Some(content) = read_entry(archive, &notes.target)? {
+                let position = format!("{}:notes", position);
+                lines.extend(paragraphs(&content)?.into_iter().map(|line| Line {
+                    position: position.clone(),
+                    line,
+                    ..Default::default()
+                }));
+            }
+        }
+    }
+    Ok(lines)
+}
+
+/// Slide part names in presentation order.
+fn slides<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<Vec<String>> {
+    let content = match read_entry(archive, PRESENTATION)? {
+        Some(content) => content,
+        None => return Ok(Vec::new()),
+    };
+    let rels = relationships(archive, PRESENTATION)?;
+
+    let mut reader = Reader::from_str(&content);
+    let mut buf = Vec::new();
+    let mut slides = Vec::new();
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) | Event::Empty(ref e) if e.local_name() == b"sldId" => {
+                for attr in e.attributes() {
+                    let attr = attr?;
+                    if attr.key.ends_with(b":id") {
+                        let id = attr.unescape_and_decode_value(&reader)?;
+                        if let Some(rel) = rels
+                            .iter()
+                            .find(|rel| rel.id == id && rel.kind == SLIDE_RELATIONSHIP)
+                        {
+                            slides.push(rel.target.clone());
+                        }
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(slides)
+}
+
+/// Text of each non-empty `<a:p>` paragraph, including those in tables.
+fn paragraphs(content: &str) -> anyhow::Result<Vec<String>> {
+    let mut reader = Reader::from_str(content);
+    let mut buf = Vec::new();
+    let mut paragraphs = Vec::new();
+    let mut paragraph = String::new();
+    let mut in_text = false;
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) if e.local_name() == b"t" => in_text = true,
+            Event::End(ref e) if e.local_name() == b"t" => in_text = false,
+            Event::Empty(ref e) if e.local_name() == b"br" => paragraph.push(' '),
+            Event::Text(ref e) if in_text => paragraph.push_str(&e.unescape_and_decode(&reader)?),
+            Event::End(ref e) if e.local_name() == b"p" => {
+                let text = paragraph.trim();
+                if !text.is_empty() {
+                    paragraphs.push(text.to_string());
+                }
+                paragraph.clear();
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(paragraphs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::slide_lines;
+    use crate::package::archive;
+    use crate::tests::lines;
+
+    const RELATIONSHIPS: &str =
+        "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+    #[test]
+    fn slides_in_presentation_order() {
+        let presentation_rels = format!(
+            r#"<Relationships>
+                <Relationship Id="rId2" Type="{0}/slide" Target="slides/slide1.xml"/>
+                <Relationship Id="rId3" Type="{0}/slide" Target="slides/slide2.xml"/>
+            </Relationships>"#,
+            RELATIONSHIPS
+        );
+        let slide_rels = format!(
+            r#"<Relationships>
+                <Relationship Id="rId1" Type="{}/notesSlide" Target="../notesSlides/notesSlide1.xml"/>
+            </Relationships>"#,
+            RELATIONSHIPS
+        );
+        let mut archive = archive(&[
+            (
+                "ppt/presentation.xml",
+                r#"<p:presentation><p:sldIdLst>
+                    <p:sldId id="257" r:id="rId3"/>
+                    <p:sldId id="256" r:id="rId2"/>
+                </p:sldIdLst></p:presentation>"#,
+            ),
+            ("ppt/_rels/presentation.xml.rels", &presentation_rels),
+            (
+                "ppt/slides/slide1.xml",
+                "<p:sld><a:p><a:r><a:t>Intro</a:t></a:r></a:p></p:sld>",
+            ),
+            (
+                "ppt/slides/slide2.xml",
+                "<p:sld><a:p><a:r><a:t>Agenda</a:t></a:r><a:br/><a:r><a:t>and goals</a:t></a:r></a:p>\
+                 <a:p></a:p><a:tbl><a:tc><a:p><a:r><a:t>Q1</a:t></a:r></a:p></a:tc></a:tbl></p:sld>",
+            ),
+            ("ppt/slides/_rels/slide2.xml.rels", &slide_rels),
+            (
+                "ppt/notesSlides/notesSlide1.xml",
+                "<p:notes><a:p><a:r><a:t>Say hello</a:t></a:r></a:p></p:notes>",
+            ),
+        ]);
+        assert_eq!(
+            lines(&slide_lines(&mut archive).unwrap()),
+            [
+                ("slide1", "Agenda and goals"),
+                ("slide1", "Q1"),
+                ("slide1:notes", "Say hello"),
+                ("slide2", "Intro"),
+            ]
+        );
+    }
+}
@@ -1,80 +1,324 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
 use std::path::Path;
 
 use anyhow::anyhow;
 use calamine::{open_workbook, DataType, Ods, Reader, Xls, Xlsb, Xlsx};
-use rayon::prelude::*;
+use quick_xml::events::Event;
+use quick_xml::Reader as XmlReader;
 use tracing::instrument;
+use zip::ZipArchive;
 
-use crate::{Collector, Line};
+use crate::package::{read_entry, relationships};
+use crate::{office_crypto, sample, sniff, Collector, Encrypted, Line};
 
-const EXTERNSIONS: [&str; 4] = ["xls", "xlsb", "xlsx", "ods"];
+const EXTENSIONS: [&str; 4] = ["xls", "xlsb", "xlsx", "ods"];
 
-#[derive(Debug, Clone)]
-pub struct SheetCollector;
+const WORKBOOK: &str = "xl/workbook.xml";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SheetCollector {
+    /// Collects rows joined with the header row instead of cells.
+    pub rows: bool,
+}
 
 impl Collector for SheetCollector {
     fn name(&self) -> &'static str {
         "sheet"
     }
 
+    fn config_digest(&self) -> String {
+        format!("{:?}", self)
+    }
+
     fn accept_extension(&self, extension: Option<&str>) -> bool {
-        extension
-            .and_then(|e| EXTERNSIONS.contains(&e).then_some(()))
-            .is_some()
+        extension.map_or(false, |e| EXTENSIONS.contains(&e))
+    }
+
+    fn accept_mime_type(&self, mime_type: &str) -> bool {
+        matches!(
+            mime_type,
+            "application/vnd.ms-excel"
+                | "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+                | "application/vnd.oasis.opendocument.spreadsheet"
+        )
     }
 
+    /// Collects cells as `Sheet1!F4` or rows as `Sheet1!A4:D4` in row mode,
+    /// then formulas (`formula:Sheet1!F4`) and comments (`comment:Sheet1!F4`).
     #[instrument]
     fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        if office_crypto::is_encrypted(path)? {
+            return Err(Encrypted::NoPassword.into());
+        }
         macro_rules! collect {
             ($t:tt) => {
-                self.collect_sheet(open_workbook::<$t<_>, _>(path)?)
+                self.collect_sheet(open_workbook::<$t<_>, _>(path)?, HashMap::new())
             };
         }
-        match path.extension().and_then(|e| e.to_str()) {
+        // misnamed files are accepted by content type, so prefer the sniffed extension
+        let extension = match sniff(&sample(path)?) {
+            Some(t) if EXTENSIONS.contains(&t.extension) => Some(t.extension),
+            _ => path.extension().and_then(|e| e.to_str()),
+        };
+        match extension {
             Some("xls") => collect!(Xls),
-            Some("xlsx") => collect!(Xlsx),
+            Some("xlsx") => self.collect_sheet(
+                open_workbook::<Xlsx<_>, _>(path)?,
+                comments(ZipArchive::new(File::open(path)?)?)?,
+            ),
             Some("xlsb") => collect!(Xlsb),
             Some("ods") => collect!(Ods),
             _ => Err(anyhow!("unsupported file: {:?}", path)),
         }
     }
+
+    /// Collects password protected xlsx and xlsb workbooks.
+    #[instrument(skip(password))]
+    fn collect_encrypted(&self, path: &Path, password: &str) -> anyhow::Result<Vec<Line>> {
+        let package = office_crypto::decrypt(path, password)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("xlsb") => self.collect_sheet(Xlsb::new(Cursor::new(package))?, HashMap::new()),
+            _ => {
+                let comments = comments(ZipArchive::new(Cursor::new(&package))?)?;
+                self.collect_sheet(Xlsx::new(Cursor::new(package))?, comments)
+            }
+        }
+    }
 }
 
 impl SheetCollector {
-    fn collect_sheet<R>(&self, mut sheet: R) -> anyhow::Result<Vec<Line>>
+    fn collect_sheet<R>(&self, mut sheet: R, mut comments: Comments) -> anyhow::Result<Vec<Line>>
     where
         R: Reader,
         R::Error: Into<anyhow::Error>,
     {
-        Ok(sheet
-            .worksheets()
-            .iter()
-            .flat_map(|(name, page)| {
-                let mut cells = page
-                    .cells()
-                    .par_bridge()
-                    .filter_map(|(r, c, data)| match data {
-                        DataType::String(s) => Some((r, c, s.clone())),
-                        DataType::DateTime(_) => Some((r, c, data.as_datetime()?.to_string())),
-                        DataType::Int(i) => Some((r, c, i.to_string())),
-                        DataType::Float(f) => Some((r, c, f.to_string())),
-                        _ => None,
-                    })
-                    .collect::<Vec<_>>();
-                cells.sort_by(
-                    |(r1, c1, _), (r2, c2, _)| {
-                        if r1 != r2 {
-                            r1.cmp(r2)
-                        } else {
-                            c1.cmp(c2)
-                        }
-                    },
-                );
-                cells.into_iter().map(move |(r, c, contents)| Line {
-                    position: format!("{}({},{})", name, r, c),
+        let mut lines = Vec::new();
+        for (name, page) in sheet.worksheets() {
+            let (top, left) = page.start().unwrap_or_default();
+            let cells = page.cells().filter_map(|(r, c, data)| {
+                let contents = match data {
+                    DataType::String(s) => s.trim().to_string(),
+                    DataType::DateTime(_) => data.as_datetime()?.to_string(),
+                    DataType::Int(i) => i.to_string(),
+                    DataType::Float(f) => f.to_string(),
+                    DataType::Bool(b) => b.to_string(),
+                    _ => return None,
+                };
+                (!contents.is_empty()).then(|| (top + r as u32, left + c as u32, contents))
+            });
+            if self.rows {
+                lines.extend(rows(&name, cells));
+            } else {
+                lines.extend(cells.map(|(r, c, contents)| Line {
+                    position: format!("{}!{}", name, reference(r, c)),
                     line: contents,
+                    ..Default::default()
+                }));
+            }
+
+            if let Some(Ok(formulas)) = sheet.worksheet_formula(&name) {
+                let (top, left) = formulas.start().unwrap_or_default();
+                lines.extend(
+                    formulas
+                        .cells()
+                        .filter(|(_, _, formula)| !formula.is_empty())
+                        .map(|(r, c, formula)| Line {
+                            position: format!(
+                                "formula:{}!{}",
+                                name,
+                                reference(top + r as u32, left + c as u32)
+                            ),
+                            line: format!("={}", formula),
+                            ..Default::default()
+                        }),
+                );
+            }
+            lines.extend(
+                comments
+                    .remove(&name)
+                    .into_iter()
+                    .flatten()
+                    .map(|(cell, comment)| Line {
+                        position: format!("comment:{}!{}", name, cell),
+                        line: comment,
+                        ..Default::default()
+                    }),
+            );
+        }
+        Ok(lines)
+    }
+}
+
+/// Rows joined with the header row, e.g. `Name: Alice | Dept: R&D`,
+/// the header is the first non-empty row and columns without headers are named by letters.
+fn rows(name: &str, cells: impl Iterator<Item = (u32, u32, String)>) -> Vec<Line> {
+    let mut rows: Vec<(u32, Vec<(u32, String)>)> = Vec::new();
+    for (r, c, contents) in cells {
+        match rows.last_mut() {
+            Some((row, cells)) if *row == r => cells.push((c, contents)),
+            _ => rows.push((r, vec![(c, contents)])),
+        }
+    }
+    let header = match rows.first() {
+        Some((_, cells)) => cells.iter().cloned().collect::<HashMap<_, _>>(),
+        None => return Vec::new(),
+    };
+    rows.iter()
+        .enumerate()
+        .map(|(i, (r, cells))| {
+            let first = cells.first().map_or(0, |(c, _)| *c);
+            let last = cells.last().map_or(0, |(c, _)| *c);
+            let columns = cells
+                .iter()
+                .filter(|_| i > 0)
+                .map(|(c, contents)| {
+                    let title = header.get(c).cloned().unwrap_or_else(|| column(*c));
+                    (title, contents.clone())
                 })
-            })
-            .collect())
+                .collect::<Vec<_>>();
+            let line = match i {
+                0 => cells
+                    .iter()
+                    .map(|(_, contents)| contents.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+                _ => columns
+                    .iter()
+                    .map(|(title, contents)| format!("{}: {}", title, contents))
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+            };
+            Line {
+                position: format!("{}!{}:{}", name, reference(*r, first), reference(*r, last)),
+                line,
+                columns,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// A1-style reference of zero-based row and column, e.g. (3, 5) => `F4`.
+fn reference(row: u32, col: u32) -> String {
+    format!("{}{}", column(col), row + 1)
+}
+
+/// Column letters of zero-based column, e.g. 27 => `AB`.
+fn column(col: u32) -> String {
+    let mut letters = Vec::new();
+    let mut n = col + 1;
+    while n > 0 {
+        n -= 1;
+        letters.push(b'A' + (n % 26) as u8);
+        n /= 26;
+    }
+    letters.iter().rev().map(|&b| b as char).collect()
+}
+
+/// Cell comments of sheets in xlsx workbooks: sheet name => [(cell reference, comment)].
+type Comments = HashMap<String, Vec<(String, String)>>;
+
+fn comments<R: Read + Seek>(mut archive: ZipArchive<R>) -> anyhow::Result<Comments> {
+    let workbook = relationships(&mut archive, "")?
+        .into_iter()
+        .find(|rel| rel.kind.ends_with("/officeDocument"))
+        .map_or_else(|| WORKBOOK.to_string(), |rel| rel.target);
+    let sheets = relationships(&mut archive, &workbook)?;
+    let content = match read_entry(&mut archive, &workbook)? {
+        Some(content) => content,
+        None => return Ok(HashMap::new()),
+    };
+
+    let mut comments = HashMap::new();
+    for (name, id) in sheet_ids(&content)? {
+        let sheet = match sheets.iter().find(|rel| rel.id == id) {
+            Some(rel) => &rel.target,
+            None => continue,
+        };
+        for rel in relationships(&mut archive, sheet)? {
+            if !rel.kind.ends_with("/comments") {
+                continue;
+            }
+            if let Some(content) = read_entry(&mut archive, &rel.target)? {
+                comments
+                    .entry(name.clone())
+                    .or_insert_with(Vec::new)
+                    .extend(sheet_comments(&content)?);
+            }
+        }
+    }
+    Ok(comments)
+}
+
+/// Names and relationship ids of sheets in workbook.xml.
+fn sheet_ids(content: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut reader = XmlReader::from_str(content);
+    let mut buf = Vec::new();
+    let mut sheets = Vec::new();
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) | Event::Empty(ref e) if e.local_name() == b"sheet" => {
+                let (mut name, mut id) = (None, None);
+                for attr in e.attributes() {
+                    let attr = attr?;
+                    match attr.key {
+                        b"name" => name = Some(attr.unescape_and_decode_value(&reader)?),
+                        b"r:id" => id = Some(attr.unescape_and_decode_value(&reader)?),
+                        _ => (),
+                    }
+                }
+                if let (Some(name), Some(id)) = (name, id) {
+                    sheets.push((name, id));
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(sheets)
+}
+
+/// Comments of a comments part, runs of rich text are concatenated.
+fn sheet_comments(content: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut reader = XmlReader::from_str(content);
+    let mut buf = Vec::new();
+    let mut comments = Vec::new();
+    let mut comment: Option<(String, String)> = None;
+    let mut in_text = false;
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) if e.local_name() == b"comment" => {
+                let mut cell = String::new();
+                for attr in e.attributes() {
+                    let attr = attr?;
+                    if attr.key == b"ref" {
+                        cell = attr.unescape_and_decode_value(&reader)?;
+                    }
+                }
+                comment = Some((cell, String::new()));
+            }
+            Event::Start(ref e) if e.local_name() == b"t" => in_text = true,
+            Event::End(ref e) if e.local_name() == b"t" => in_text = false,
+            Event::Text(ref e) if in_text => {
+                if let Some((_, text)) = comment.as_mut() {
+                    text.push_str(&e.unescape_and_decode(&reader)?);
+                }
+            }
+            Event::End(ref e) if e.local_name() == b"comment" => {
+                if let Some((cell, text)) = comment.take() {
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if !text.is_empty() {
+                        comments.push((cell, text));
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
     }
+    Ok(comments)
 }
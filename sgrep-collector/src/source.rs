@@ -0,0 +1,680 @@
+use std::path::Path;
+
+use anyhow::anyhow;
+use regex::Regex;
+use tracing::instrument;
+
+use crate::utf8::{detect, read_text, sample};
+use crate::{Collector, Line};
+
+const EXTENSIONS: [&str; 18] = [
+    "rs", "py", "pyi", "go", "js", "jsx", "mjs", "cjs", "ts", "tsx", "c", "h", "cc", "cpp", "cxx",
+    "hh", "hpp", "hxx",
+];
+/// Keywords followed by parentheses, which look like calls or declarations of functions.
+const KEYWORDS: [&str; 16] = [
+    "if", "for", "while", "switch", "catch", "return", "sizeof", "else", "do", "case", "function",
+    "with", "elif", "new", "typeof", "await",
+];
+
+/// Collects source files, lines are located by the enclosing symbol,
+/// e.g. `L120 in fn Engine::indexing`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SourceCollector {
+    /// Indexes sub-tokens of identifiers, e.g. `index_files` is also searchable by `files`.
+    pub split_identifiers: bool,
+    /// Collects comments and docstrings only.
+    pub comments: bool,
+}
+
+impl Collector for SourceCollector {
+    fn name(&self) -> &'static str {
+        "source"
+    }
+
+    fn config_digest(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        extension.map_or(false, |e| EXTENSIONS.contains(&e))
+    }
+
+    // `.ts` is also the extension of MPEG transport streams
+    fn should_collect(&self, path: &Path) -> anyhow::Result<bool> {
+        let extension = path.extension().and_then(|e| e.to_str());
+        Ok(self.accept_extension(extension) && detect(&sample(path)?).is_some())
+    }
+
+    fn is_text(&self) -> bool {
+        true
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        let language = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(Language::from_extension)
+            .ok_or_else(|| anyhow!("unknown language: {:?}", path))?;
+        let text = read_text(path)?;
+        Ok(self.parse(language, &text))
+    }
+}
+
+impl SourceCollector {
+    fn parse(&self, language: Language, text: &str) -> Vec<Line> {
+        let syntax = Syntax::new(language);
+        let mut scanner = Scanner::new(language);
+        let mut scopes = Vec::new();
+        // nesting of braces, or of brackets for Python whose scopes are indented
+        let mut depth = 0usize;
+        let mut pending = None;
+        let mut lines = Vec::new();
+        for (i, text) in text.lines().enumerate() {
+            let continued = scanner.in_string() || (language == Language::Python && depth > 0);
+            let (code, comment) = scanner.scan(text);
+            let before = syntax.context(&scopes, None);
+            // context of the symbol declared on the line, whose body may close on it too
+            let mut declared = None;
+            if language == Language::Python {
+                if !continued && !code.trim().is_empty() {
+                    let indent = indentation(text);
+                    while matches!(scopes.last(), Some(Scope { level, .. }) if *level >= indent) {
+                        scopes.pop();
+                    }
+                    if let Some(symbol) = syntax.declaration(&code, &scopes) {
+                        scopes.push(Scope {
+                            symbol,
+                            level: indent,
+                        });
+                    }
+                }
+                for c in code.chars() {
+                    match c {
+                        '(' | '[' | '{' => depth += 1,
+                        ')' | ']' | '}' => depth = depth.saturating_sub(1),
+                        _ => (),
+                    }
+                }
+            } else {
+                if let Some(symbol) = syntax.declaration(&code, &scopes) {
+                    pending = Some(symbol);
+                }
+                for c in code.chars() {
+                    match c {
+                        '{' => {
+                            depth += 1;
+                            if let Some(symbol) = pending.take() {
+                                scopes.push(Scope {
+                                    symbol,
+                                    level: depth,
+                                });
+                                declared = syntax.context(&scopes, None);
+                            }
+                        }
+                        '}' => {
+                            if matches!(scopes.last(), Some(Scope { level, .. }) if *level == depth)
+                            {
+                                scopes.pop();
+                            }
+                            depth = depth.saturating_sub(1);
+                        }
+                        // declarations without bodies
+                        ';' => pending = None,
+                        _ => (),
+                    }
+                }
+            }
+
+            let line = if self.comments {
+                comment
+                    .trim()
+                    .trim_start_matches(|c: char| matches!(c, '/' | '*' | '!' | '#' | '<'))
+                    .trim()
+                    .to_string()
+            } else {
+                text.trim_end().to_string()
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            // lines belong to the symbols declared on them, and closing lines to the closed ones
+            let context = if language == Language::Python {
+                syntax.context(&scopes, None)
+            } else if declared.is_some() {
+                declared
+            } else if pending.is_some() {
+                syntax.context(&scopes, pending.as_ref())
+            } else {
+                before
+            };
+            let terms = if self.split_identifiers {
+                split_identifiers(if self.comments { &line } else { &code })
+            } else {
+                Vec::new()
+            };
+            lines.push(Line {
+                position: match context {
+                    Some(context) => format!("L{} in {}", i + 1, context),
+                    None => format!("L{}", i + 1),
+                },
+                line,
+                terms,
+                ..Default::default()
+            });
+        }
+        lines
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    Rust,
+    Python,
+    Go,
+    JavaScript,
+    C,
+}
+
+impl Language {
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "rs" => Some(Self::Rust),
+            "py" | "pyi" => Some(Self::Python),
+            "go" => Some(Self::Go),
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => Some(Self::JavaScript),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => Some(Self::C),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Symbol {
+    kind: String,
+    name: String,
+}
+
+/// An enclosing symbol and the nesting depth or indentation of its body.
+#[derive(Debug)]
+struct Scope {
+    symbol: Symbol,
+    level: usize,
+}
+
+/// Patterns of declarations, matched on code with comments and contents of strings removed.
+struct Syntax {
+    /// Patterns with groups `name`, optional `kind` and `scope` (the receiver of Go methods),
+    /// their default kind and whether they are declared in functions.
+    patterns: Vec<(Regex, &'static str, bool)>,
+    separator: &'static str,
+}
+
+impl Syntax {
+    fn new(language: Language) -> Self {
+        let (patterns, separator): (&[(&str, &'static str, bool)], _) = match language {
+            Language::Rust => (
+                &[
+                    (
+                        r"\b(?P<kind>fn|struct|enum|trait|mod|union)\s+(?P<name>\w+)",
+                        "",
+                        true,
+                    ),
+                    // after functions returning `impl Trait`
+                    (
+                        r"\bimpl\b(?:\s*<.*?>)?\s+(?:[\w:]+(?:<.*?>)?\s+for\s+)?(?:\w+::)*(?P<name>\w+)",
+                        "impl",
+                        true,
+                    ),
+                ],
+                "::",
+            ),
+            Language::Python => (
+                &[(
+                    r"^\s*(?:async\s+)?(?P<kind>def|class)\s+(?P<name>\w+)",
+                    "",
+                    true,
+                )],
+                ".",
+            ),
+            Language::Go => (
+                &[
+                    (
+                        r"^\s*func\s*(?:\(\s*(?:\w+\s+)?\*?\s*(?P<scope>\w+)[^)]*\)\s*)?(?P<name>\w+)",
+                        "func",
+                        true,
+                    ),
+                    (
+                        r"^\s*type\s+(?P<name>\w+)\s+(?P<kind>struct|interface)",
+                        "",
+                        true,
+                    ),
+                ],
+                ".",
+            ),
+            Language::JavaScript => (
+                &[
+                    (
+                        r"\b(?P<kind>class|interface|namespace|enum)\s+(?P<name>\w+)",
+                        "",
+                        true,
+                    ),
+                    (r"\bfunction\b\*?\s*(?P<name>\w+)", "function", true),
+                    (
+                        r"\b(?:const|let|var)\s+(?P<name>\w+)\s*(?::[^=]*)?=\s*(?:async\s+)?(?:function\b|(?:\([^)]*\)|\w+)\s*(?::[^=]*)?=>\s*\{)",
+                        "function",
+                        true,
+                    ),
+                    // methods of classes
+                    (
+                        r"^\s*(?:(?:public|private|protected|static|async|readonly|override|abstract|get|set)\s+)*\*?(?P<name>\w+)\s*(?:<[^>]*>)?\s*\([^)]*\)\s*(?::[^{]*)?\{",
+                        "function",
+                        true,
+                    ),
+                ],
+                ".",
+            ),
+            Language::C => (
+                &[
+                    // not functions returning them, e.g. `struct foo *make_foo(int x) {`
+                    (
+                        r"^\s*(?:template\s*<.*>\s*)?(?:typedef\s+)?(?P<kind>class|struct|namespace|union|enum)\s+(?:class\s+)?(?P<name>\w+)[^;(]*$",
+                        "",
+                        true,
+                    ),
+                    (
+                        r"^(?:\s*[\w:*&<>,~]+\s+)*?[*&]*(?P<name>[A-Za-z_~][\w:~]*)\s*\([^;]*$",
+                        "function",
+                        false,
+                    ),
+                ],
+                "::",
+            ),
+        };
+        Self {
+            patterns: patterns
+                .iter()
+                .map(|&(pattern, kind, nested)| (Regex::new(pattern).unwrap(), kind, nested))
+                .collect(),
+            separator,
+        }
+    }
+
+    fn declaration(&self, code: &str, scopes: &[Scope]) -> Option<Symbol> {
+        let in_function = matches!(scopes.last(), Some(scope) if scope.symbol.kind == "function");
+        self.patterns
+            .iter()
+            .filter(|(_, _, nested)| *nested || !in_function)
+            .find_map(|(pattern, kind, _)| {
+                let caps = pattern.captures(code)?;
+                let name = caps.name("name")?.as_str();
+                if KEYWORDS.contains(&name) {
+                    return None;
+                }
+                let name = match caps.name("scope") {
+                    Some(scope) => format!("{}{}{}", scope.as_str(), self.separator, name),
+                    None => name.to_string(),
+                };
+                Some(Symbol {
+                    kind: caps.name("kind").map_or(*kind, |k| k.as_str()).to_string(),
+                    name,
+                })
+            })
+    }
+
+    /// The innermost symbol qualified by enclosing ones, e.g. `fn Engine::indexing`,
+    /// `pending` is the symbol whose body is not opened yet.
+    fn context(&self, scopes: &[Scope], pending: Option<&Symbol>) -> Option<String> {
+        let symbols = scopes
+            .iter()
+            .map(|scope| &scope.symbol)
+            .chain(pending)
+            .collect::<Vec<_>>();
+        let innermost = symbols.last()?;
+        let names = symbols
+            .iter()
+            .map(|symbol| symbol.name.as_str())
+            .collect::<Vec<_>>();
+        Some(format!("{} {}", innermost.kind, names.join(self.separator)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Code,
+    /// In a block comment.
+    Comment,
+    /// In a string ending with `end`, docstrings of Python are collected as comments.
+    String {
+        end: String,
+        raw: bool,
+        multiline: bool,
+        doc: bool,
+    },
+}
+
+/// Splits lines into code and comments across lines.
+struct Scanner {
+    language: Language,
+    state: State,
+}
+
+impl Scanner {
+    fn new(language: Language) -> Self {
+        Self {
+            language,
+            state: State::Code,
+        }
+    }
+
+    fn in_string(&self) -> bool {
+        matches!(self.state, State::String { .. })
+    }
+
+    /// Code of the line with comments and contents of strings removed, and text of comments.
+    fn scan(&mut self, line: &str) -> (String, String) {
+        let mut code = String::new();
+        let mut comment = String::new();
+        let mut i = 0;
+        while i < line.len() {
+            let rest = &line[i..];
+            match &self.state {
+                State::Comment => match rest.find("*/") {
+                    Some(end) => {
+                        comment.push_str(&rest[..end]);
+                        comment.push(' ');
+                        i += end + 2;
+                        self.state = State::Code;
+                    }
+                    None => {
+                        comment.push_str(rest);
+                        break;
+                    }
+                },
+                State::String { end, raw, doc, .. } => match string_end(rest, end, *raw) {
+                    Some(j) => {
+                        if *doc {
+                            comment.push_str(&rest[..j]);
+                        }
+                        code.push_str(end);
+                        i += j + end.len();
+                        self.state = State::Code;
+                    }
+                    None => {
+                        if *doc {
+                            comment.push_str(rest);
+                        }
+                        break;
+                    }
+                },
+                State::Code => {
+                    let line_comment = match self.language {
+                        Language::Python => "#",
+                        _ => "//",
+                    };
+                    if let Some(text) = rest.strip_prefix(line_comment) {
+                        comment.push_str(text);
+                        break;
+                    }
+                    if self.language != Language::Python && rest.starts_with("/*") {
+                        self.state = State::Comment;
+                        i += 2;
+                        continue;
+                    }
+                    let after_word = code
+                        .chars()
+                        .last()
+                        .map_or(false, |c| c.is_alphanumeric() || c == '_');
+                    if let Some((start, end, raw, multiline)) = self.string_start(rest, after_word)
+                    {
+                        let doc = self.language == Language::Python
+                            && end.len() == 3
+                            && code.trim().is_empty();
+                        code.push_str(&rest[..start]);
+                        i += start;
+                        self.state = State::String {
+                            end,
+                            raw,
+                            multiline,
+                            doc,
+                        };
+                        continue;
+                    }
+                    let c = rest.chars().next().unwrap();
+                    code.push(c);
+                    i += c.len_utf8();
+                }
+            }
+        }
+        if matches!(
+            self.state,
+            State::String {
+                multiline: false,
+                ..
+            }
+        ) {
+            self.state = State::Code;
+        }
+        (code, comment)
+    }
+
+    /// Length of the opening quote, the closing quote, whether it's raw and multiline.
+    fn string_start(&self, rest: &str, after_word: bool) -> Option<(usize, String, bool, bool)> {
+        let mut chars = rest.chars();
+        let first = chars.next()?;
+        match self.language {
+            Language::Python => {
+                let prefix = if after_word {
+                    0
+                } else {
+                    rest.find(|c: char| !matches!(c, 'r' | 'R' | 'b' | 'B' | 'u' | 'U' | 'f' | 'F'))
+                        .filter(|&len| len <= 2)?
+                };
+                let raw = rest[..prefix].contains(|c: char| c == 'r' || c == 'R');
+                let quoted = &rest[prefix..];
+                ["\"\"\"", "'''", "\"", "'"]
+                    .iter()
+                    .find(|quote| quoted.starts_with(*quote))
+                    .map(|quote| {
+                        (
+                            prefix + quote.len(),
+                            quote.to_string(),
+                            raw,
+                            quote.len() == 3,
+                        )
+                    })
+            }
+            Language::Rust => {
+                let raw = rest
+                    .strip_prefix("br")
+                    .or_else(|| rest.strip_prefix('r'))
+                    .filter(|_| !after_word)
+                    .and_then(|raw| {
+                        let hashes = raw.len() - raw.trim_start_matches('#').len();
+                        raw[hashes..].starts_with('"').then(|| (raw, hashes))
+                    });
+                if let Some((raw, hashes)) = raw {
+                    let start = rest.len() - raw.len() + hashes + 1;
+                    return Some((start, format!("\"{}", "#".repeat(hashes)), true, true));
+                }
+                match (first, chars.next(), chars.next()) {
+                    ('b', Some('"'), _) if !after_word => Some((2, "\"".into(), false, true)),
+                    ('"', ..) => Some((1, "\"".into(), false, true)),
+                    // character literals, not lifetimes
+                    ('\'', Some('\\'), _) | ('\'', Some(_), Some('\'')) => {
+                        Some((1, "'".into(), false, false))
+                    }
+                    _ => None,
+                }
+            }
+            _ => match first {
+                '"' | '\'' => Some((1, first.to_string(), false, false)),
+                '`' if self.language != Language::C => Some((1, "`".into(), true, true)),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Offset of the closing quote, escaped ones are skipped unless the string is raw.
+fn string_end(rest: &str, end: &str, raw: bool) -> Option<usize> {
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if rest[i..].starts_with(end) {
+            return Some(i);
+        }
+        if c == '\\' && !raw {
+            chars.next();
+        }
+    }
+    None
+}
+
+fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Lowercase sub-tokens of `snake_case` and `CamelCase` identifiers.
+pub fn split_identifiers(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for identifier in text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_')) {
+        let words = words(identifier);
+        if words.len() < 2 {
+            continue;
+        }
+        for word in words.into_iter().filter(|word| word.len() > 1) {
+            let word = word.to_ascii_lowercase();
+            if !terms.contains(&word) {
+                terms.push(word);
+            }
+        }
+    }
+    terms
+}
+
+/// Words of an ASCII identifier, e.g. `parseHTTPResponse_v2` => `parse`, `HTTP`, `Response`, `v2`.
+fn words(identifier: &str) -> Vec<&str> {
+    let chars = identifier.as_bytes();
+    let mut words = Vec::new();
+    let mut start = 0;
+    for (i, &c) in chars.iter().enumerate() {
+        if c == b'_' {
+            if start < i {
+                words.push(&identifier[start..i]);
+            }
+            start = i + 1;
+            continue;
+        }
+        let boundary = c.is_ascii_uppercase()
+            && match i.checked_sub(1).map(|j| chars[j]) {
+                Some(prev) if prev.is_ascii_lowercase() || prev.is_ascii_digit() => true,
+                Some(prev) if prev.is_ascii_uppercase() => {
+                    chars.get(i + 1).map_or(false, u8::is_ascii_lowercase)
+                }
+                _ => false,
+            };
+        if boundary && start < i {
+            words.push(&identifier[start..i]);
+            start = i;
+        }
+    }
+    if start < identifier.len() {
+        words.push(&identifier[start..]);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_identifiers, words, Language, SourceCollector};
+    use crate::tests::lines;
+
+    #[test]
+    fn identifier_words() {
+        assert_eq!(
+            words("parseHTTPResponse_v2"),
+            vec!["parse", "HTTP", "Response", "v2"]
+        );
+        assert_eq!(
+            split_identifiers("fn parseHTTPResponse_v2(x: u8, index_files: Files)"),
+            vec!["parse", "http", "response", "v2", "index", "files"]
+        );
+    }
+
+    #[test]
+    fn rust_strings_and_chars() {
+        let text = r##"fn open<'a>(path: &'a str) -> &'a str {
+    let brace = '{';
+    let url = r#"http://example.com/{id}"#;
+    path
+}
+fn close() {}
+"##;
+        assert_eq!(
+            lines(&SourceCollector::default().parse(Language::Rust, text)),
+            [
+                ("L1 in fn open", "fn open<'a>(path: &'a str) -> &'a str {"),
+                ("L2 in fn open", "    let brace = '{';"),
+                (
+                    "L3 in fn open",
+                    r##"    let url = r#"http://example.com/{id}"#;"##
+                ),
+                ("L4 in fn open", "    path"),
+                ("L5 in fn open", "}"),
+                ("L6 in fn close", "fn close() {}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn python_docstrings() {
+        let text = r#"class Index:
+    def search(self, query):
+        """Searches the index.
+
+        Returns matched lines.
+        """
+        # by the query parser
+        return self.parse(query)  # parsed
+"#;
+        let collector = SourceCollector {
+            comments: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            lines(&collector.parse(Language::Python, text)),
+            [
+                ("L3 in def Index.search", "Searches the index."),
+                ("L5 in def Index.search", "Returns matched lines."),
+                ("L7 in def Index.search", "by the query parser"),
+                ("L8 in def Index.search", "parsed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn c_functions_returning_structs() {
+        let text = "struct foo {
+    int x;
+};
+struct foo *make_foo(int x) {
+    return NULL;
+}
+";
+        assert_eq!(
+            lines(&SourceCollector::default().parse(Language::C, text)),
+            [
+                ("L1 in struct foo", "struct foo {"),
+                ("L2 in struct foo", "    int x;"),
+                ("L3 in struct foo", "};"),
+                ("L4 in function make_foo", "struct foo *make_foo(int x) {"),
+                ("L5 in function make_foo", "    return NULL;"),
+                ("L6 in function make_foo", "}"),
+            ]
+        );
+    }
+}
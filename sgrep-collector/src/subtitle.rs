@@ -0,0 +1,235 @@
+use std::path::Path;
+
+use tracing::instrument;
+
+use crate::utf8::read_text;
+use crate::{Collector, Line};
+
+const EXTENSIONS: [&str; 4] = ["srt", "vtt", "ass", "ssa"];
+
+/// Collects cues of SubRip, WebVTT and SubStation Alpha subtitles located by their start time,
+/// e.g. `00:12:34.500`, styling tags are removed and lines of a cue are joined.
+#[derive(Debug, Clone, Copy)]
+pub struct SubtitleCollector;
+
+impl Collector for SubtitleCollector {
+    fn name(&self) -> &'static str {
+        "subtitle"
+    }
+
+    fn accept_extension(&self, extension: Option<&str>) -> bool {
+        extension.map_or(false, |e| EXTENSIONS.contains(&e))
+    }
+
+    fn is_text(&self) -> bool {
+        true
+    }
+
+    #[instrument]
+    fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
+        let text = read_text(path)?;
+        Ok(match path.extension().and_then(|e| e.to_str()) {
+            Some("ass") | Some("ssa") => dialogues(&text),
+            _ => cues(&text),
+        })
+    }
+}
+
+/// The line of a cue located by its start time, `None` if the cue is empty.
+fn cue(start: String, line: String) -> Option<Line> {
+    (!line.is_empty()).then(|| Line {
+        position: start,
+        line,
+        ..Default::default()
+    })
+}
+
+/// Cues of SubRip and WebVTT, blocks separated by blank lines with a timing line like
+/// `00:12:34,500 --> 00:12:36,000`, other blocks like the header and notes are skipped.
+fn cues(text: &str) -> Vec<Line> {
+    let mut cues = Vec::new();
+    let mut lines = text.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let start = match line.split_once("-->") {
+            Some((start, _)) => match timestamp(start.trim()) {
+                Some(start) => start,
+                None => continue,
+            },
+            None => continue,
+        };
+        let text = lines
+            .by_ref()
+            .take_while(|line| !line.is_empty())
+            .map(strip_tags)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        cues.extend(cue(start, text.join(" ")));
+    }
+    cues
+}
+
+/// Dialogues of SubStation Alpha, fields are ordered by the `Format:` line of `[Events]`.
+fn dialogues(text: &str) -> Vec<Line> {
+    let mut dialogues = Vec::new();
+    let mut format = vec![
+        "Layer", "Start", "End", "Style", "Name", "MarginL", "MarginR", "MarginV", "Effect", "Text",
+    ];
+    let mut events = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) if events => (key.trim(), value.trim()),
+            _ => continue,
+        };
+        if key == "Format" {
+            format = value.split(',').map(str::trim).collect();
+        } else if key == "Dialogue" {
+            // the text is the last field, which may contain commas
+            let fields = value.splitn(format.len(), ',').collect::<Vec<_>>();
+            let field = |name| {
+                format
+                    .iter()
+                    .position(|&f| f == name)
+                    .and_then(|i| fields.get(i))
+            };
+            if let (Some(start), Some(text)) = (field("Start"), field("Text")) {
+                if let Some(start) = timestamp(start.trim()) {
+                    let text = text.replace("\\N", " ").replace("\\n", " ");
+                    dialogues.extend(cue(start, strip_tags(&text)));
+                }
+            }
+        }
+    }
+    dialogues
+}
+
+/// Normalizes times like `1:02:03,5`, `02:03.500` or `0:02:03.50` to `01:02:03.500`.
+fn timestamp(time: &str) -> Option<String> {
+    let (time, fraction) = time
+        .split_once(|c: char| c == ',' || c == '.')
+        .unwrap_or((time, "0"));
+    let mut parts = time
+        .split(':')
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if parts.is_empty() || parts.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    while parts.len() < 3 {
+        parts.insert(0, 0);
+    }
+    let millis = format!("{:0<3}", fraction.get(..3).unwrap_or(fraction));
+    Some(format!(
+        "{:02}:{:02}:{:02}.{}",
+        parts[0], parts[1], parts[2], millis
+    ))
+}
+
+/// Removes HTML-like tags (`<i>`, `<c.yellow>`, `<00:00:01.000>`) and override blocks
+/// of SubStation Alpha (`{\an8}`), voices of WebVTT like `<v Alice>` are kept as `Alice: `.
+fn strip_tags(line: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = line;
+    while let Some(i) = rest.find(|c: char| c == '<' || c == '{') {
+        stripped.push_str(&rest[..i]);
+        let close = if rest[i..].starts_with('<') { '>' } else { '}' };
+        match rest[i..].find(close) {
+            Some(j) => {
+                let tag = &rest[i + 1..i + j];
+                // voices may have classes, e.g. `<v.loud Alice>`
+                if tag.starts_with("v ") || tag.starts_with("v.") {
+                    if let Some((_, name)) = tag.split_once(' ') {
+                        stripped.push_str(name.trim());
+                        stripped.push_str(": ");
+                    }
+                }
+                rest = &rest[i + j + 1..];
+            }
+            None => {
+                stripped.push_str(&rest[i..]);
+                rest = "";
+            }
+        }
+    }
+    stripped.push_str(rest);
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cues, dialogues, strip_tags, timestamp};
+    use crate::tests::lines;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp("1:02:03,5").as_deref(), Some("01:02:03.500"));
+        assert_eq!(timestamp("02:03.500").as_deref(), Some("00:02:03.500"));
+        assert_eq!(timestamp("0:02:03.50").as_deref(), Some("00:02:03.500"));
+        assert_eq!(timestamp("00:00:01").as_deref(), Some("00:00:01.000"));
+        assert_eq!(timestamp("1:2:3:4.000"), None);
+        assert_eq!(timestamp("NOTE"), None);
+        assert_eq!(timestamp("00:01.5x"), None);
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            strip_tags("<i>Hello</i>, <c.yellow>world</c>"),
+            "Hello, world"
+        );
+        assert_eq!(strip_tags("{\\an8}Top <00:00:01.000>line"), "Top line");
+        assert_eq!(strip_tags("<v Alice>Hi"), "Alice: Hi");
+        assert_eq!(strip_tags("<v.loud Bob>Hey</v>"), "Bob: Hey");
+        assert_eq!(strip_tags("1 < 2"), "1 < 2");
+    }
+
+    #[test]
+    fn srt_and_vtt_cues() {
+        let srt = "1
+00:00:01,000 --> 00:00:02,000
+<i>Hello</i>
+world
+
+2
+00:00:03,500 --> 00:00:04,000
+Again
+";
+        assert_eq!(
+            lines(&cues(srt)),
+            [("00:00:01.000", "Hello world"), ("00:00:03.500", "Again")]
+        );
+
+        let vtt = "WEBVTT
+
+NOTE a comment
+
+intro
+01:02.000 --> 01:03.000 align:start
+<v Alice>Hi
+";
+        assert_eq!(lines(&cues(vtt)), [("00:01:02.000", "Alice: Hi")]);
+    }
+
+    #[test]
+    fn ass_dialogues() {
+        let ass = "[Script Info]
+Title: Test
+
+[V4+ Styles]
+Format: Name, Fontname
+Style: Default,Arial
+
+[Events]
+Format: Layer, Start, End, Style, Text
+Dialogue: 0,0:00:01.50,0:00:03.00,Default,{\\i1}Well, well,\\Nwell.
+Comment: 0,0:00:04.00,0:00:05.00,Default,skipped
+";
+        assert_eq!(
+            lines(&dialogues(ass)),
+            [("00:00:01.500", "Well, well, well.")]
+        );
+    }
+}
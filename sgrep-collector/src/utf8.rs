@@ -1,12 +1,19 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::Read;
 use std::path::Path;
 
+use anyhow::anyhow;
+use chardetng::EncodingDetector;
+use encoding_rs::{DecoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8};
 use tracing::{debug, instrument};
-use utf8_chars::BufReadCharsExt;
 
 use crate::{Collector, Line};
 
+/// Bytes read from the head of a file to detect its encoding.
+pub(crate) const SAMPLE_SIZE: u64 = 64 * 1024;
+
+/// Collects text files, legacy encodings (GBK, Big5, Shift_JIS, UTF-16, ...) are detected
+/// and transcoded to UTF-8.
 #[derive(Debug, Clone, Copy)]
 pub struct UTF8Collector;
 
@@ -16,28 +23,168 @@ impl Collector for UTF8Collector {
     }
 
     fn should_collect(&self, path: &Path) -> anyhow::Result<bool> {
-        let mut f = BufReader::new(File::open(path)?);
-        for c in f.chars() {
-            if c.is_err() {
-                return Ok(false);
-            }
-        }
-        Ok(true)
+        Ok(detect(&sample(path)?).is_some())
+    }
+
+    fn is_fallback(&self) -> bool {
+        true
+    }
+
+    fn is_text(&self) -> bool {
+        true
     }
 
     #[instrument]
     fn collect(&self, path: &Path) -> anyhow::Result<Vec<Line>> {
-        BufReader::new(File::open(path)?)
+        Ok(read_text(path)?
             .lines()
             .enumerate()
             .map(|(i, line)| {
                 let l = Line {
                     position: (i + 1).to_string(),
-                    line: line?,
+                    line: line.to_string(),
+                    ..Default::default()
                 };
                 debug!("collect line: {:?}", l);
-                Ok(l)
+                l
+            })
+            .collect())
+    }
+}
+
+/// Head of the file to detect its content type and encoding.
+pub fn sample(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let mut sample = Vec::new();
+    File::open(path)?
+        .take(SAMPLE_SIZE)
+        .read_to_end(&mut sample)?;
+    Ok(sample)
+}
+
+/// Reads the whole file decoded by the encoding detected from its head.
+pub(crate) fn read_text(path: &Path) -> anyhow::Result<String> {
+    let content = std::fs::read(path)?;
+    let encoding = detect(&content[..content.len().min(SAMPLE_SIZE as usize)])
+        .ok_or_else(|| anyhow!("not a text file: {:?}", path))?;
+    debug!("decode {:?} as {}", path, encoding.name());
+    let (text, _) = encoding.decode_with_bom_removal(&content);
+    Ok(text.into_owned())
+}
+
+/// Detects the encoding by BOM, UTF-16 zero bytes, UTF-8 validity and then statistics,
+/// returns `None` for binary content.
+pub(crate) fn detect(sample: &[u8]) -> Option<&'static Encoding> {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return Some(encoding);
+    }
+    let encoding = match (utf16(sample), std::str::from_utf8(sample)) {
+        (Some(encoding), _) => encoding,
+        (None, Ok(_)) => UTF_8,
+        // the sample may end in the middle of a character
+        (None, Err(err)) if err.error_len().is_none() => UTF_8,
+        (None, Err(_)) => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(sample, true);
+            detector.guess(None, false)
+        }
+    };
+    is_text(encoding, sample).then_some(encoding)
+}
+
+/// Guesses BOM-less UTF-16 by the zero high bytes of ASCII characters.
+fn utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let units = sample.len() / 2;
+    let (mut even, mut odd) = (0, 0);
+    for unit in sample.chunks_exact(2) {
+        even += (unit[0] == 0) as usize;
+        odd += (unit[1] == 0) as usize;
+    }
+    if units == 0 {
+        None
+    } else if odd * 2 > units && even * 10 < units {
+        Some(UTF_16LE)
+    } else if even * 2 > units && odd * 10 < units {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Text must decode without errors and contain few control characters.
+fn is_text(encoding: &'static Encoding, sample: &[u8]) -> bool {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let capacity = decoder
+        .max_utf8_buffer_length_without_replacement(sample.len())
+        .unwrap_or_default();
+    let mut text = String::with_capacity(capacity);
+    let (result, _) = decoder.decode_to_string_without_replacement(sample, &mut text, false);
+    if result != DecoderResult::InputEmpty || text.contains('\0') {
+        return false;
+    }
+    let controls = text
+        .chars()
+        .filter(|&c| c.is_control() && !c.is_whitespace() && c != '\u{1b}')
+        .count();
+    controls * 100 <= text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{GBK, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8};
+
+    use super::{detect, is_text};
+
+    fn encode_utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|u| {
+                if big_endian {
+                    u.to_be_bytes()
+                } else {
+                    u.to_le_bytes()
+                }
             })
             .collect()
     }
+
+    #[test]
+    fn utf8() {
+        assert_eq!(detect(b"plain ASCII\n"), Some(UTF_8));
+        assert_eq!(detect("h\u{e9}llo w\u{f6}rld".as_bytes()), Some(UTF_8));
+        assert_eq!(detect(b"\xEF\xBB\xBFwith BOM"), Some(UTF_8));
+        assert_eq!(detect(b""), Some(UTF_8));
+        // the sample ends in the middle of `中`
+        assert_eq!(detect(&"text 中".as_bytes()[..7]), Some(UTF_8));
+    }
+
+    #[test]
+    fn utf16_with_and_without_bom() {
+        let text = "Hello, world\r\n";
+        let le = [&b"\xFF\xFE"[..], &encode_utf16(text, false)].concat();
+        let be = [&b"\xFE\xFF"[..], &encode_utf16(text, true)].concat();
+        assert_eq!(detect(&le), Some(UTF_16LE));
+        assert_eq!(detect(&be), Some(UTF_16BE));
+        assert_eq!(detect(&le[2..]), Some(UTF_16LE));
+        assert_eq!(detect(&be[2..]), Some(UTF_16BE));
+    }
+
+    #[test]
+    fn legacy_encodings() {
+        let (gbk, _, _) =
+            GBK.encode("这是一个用于检测编码的简体中文文本文件，其中包含常见的汉字和标点符号。");
+        assert_eq!(detect(&gbk), Some(GBK));
+        let (shift_jis, _, _) = SHIFT_JIS.encode(
+            "これは文字コードを判定するための日本語のテキストです。ひらがなとカタカナを含みます。",
+        );
+        assert_eq!(detect(&shift_jis), Some(SHIFT_JIS));
+    }
+
+    #[test]
+    fn binary() {
+        let elf = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x03\0\x3e\0\x01\0\0\0";
+        assert_eq!(detect(elf), None);
+        assert_eq!(detect(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\x01\0"), None);
+        assert!(!is_text(UTF_8, b"a\0b"));
+        assert!(!is_text(UTF_8, b"\x01\x02\x03 mostly controls"));
+        assert!(is_text(UTF_8, b"\x1b[31mred\x1b[0m\n"));
+    }
 }
@@ -0,0 +1,157 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, bail};
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use tempfile::NamedTempFile;
+use tracing::debug;
+use xz2::read::XzDecoder;
+use zip::ZipArchive;
+
+/// Separator between an archive path and the entry inside it, e.g. `backup.zip!/docs/spec.pdf`.
+pub const SEPARATOR: &str = "!/";
+/// Archive entries and decompressed files larger than this are not collected.
+pub const MAX_SPOOLED_SIZE: u64 = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+    TarBz2,
+    TarXz,
+    TarZst,
+}
+
+impl Format {
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Format::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Format::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else if name.ends_with(".tar.bz2") || name.ends_with(".tbz2") {
+            Some(Format::TarBz2)
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Some(Format::TarXz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(Format::TarZst)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    Format::of(path).is_some()
+}
+
+/// Joins an archive path and an entry name into a virtual path.
+pub fn virtual_path(archive: &Path, entry: &str) -> String {
+    format!(
+        "{}{}{}",
+        archive.to_string_lossy(),
+        SEPARATOR,
+        entry.trim_start_matches('/')
+    )
+}
+
+/// Splits a virtual path into the archive path and the entry name.
+pub fn split(path: &str) -> Option<(&str, &str)> {
+    path.split_once(SEPARATOR)
+}
+
+/// Copies content into a temporary file keeping its file name,
+/// so that collectors can dispatch on the extension, content over `limit` bytes fails.
+pub fn spool(name: &str, reader: &mut dyn Read, limit: u64) -> anyhow::Result<NamedTempFile> {
+    let file_name = Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let mut file = tempfile::Builder::new().suffix(file_name).tempfile()?;
+    // copy one more byte to tell content of the limit from larger one
+    if std::io::copy(&mut reader.take(limit + 1), &mut file)? > limit {
+        bail!("size of {} exceeds {} bytes", name, limit);
+    }
+    file.flush()?;
+    Ok(file)
+}
+
+/// Visits every regular file in the archive with its entry name and content reader.
+pub fn visit(
+    path: &Path,
+    mut visitor: impl FnMut(&str, &mut dyn Read) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let format = Format::of(path).ok_or_else(|| anyhow!("unsupported archive: {:?}", path))?;
+    debug!("visit {:?} archive: {:?}", format, path);
+    let file = File::open(path)?;
+    match format {
+        Format::Zip => {
+            let mut archive = ZipArchive::new(file)?;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i)?;
+                if entry.is_file() {
+                    let name = entry.name().to_string();
+                    visitor(&name, &mut entry)?;
+                }
+            }
+            Ok(())
+        }
+        Format::Tar => visit_tar(file, visitor),
+        Format::TarGz => visit_tar(MultiGzDecoder::new(file), visitor),
+        Format::TarBz2 => visit_tar(MultiBzDecoder::new(file), visitor),
+        Format::TarXz => visit_tar(XzDecoder::new(file), visitor),
+        Format::TarZst => visit_tar(zstd::Decoder::new(file)?, visitor),
+    }
+}
+
+fn visit_tar(
+    reader: impl Read,
+    mut visitor: impl FnMut(&str, &mut dyn Read) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() {
+            let name = entry.path()?.to_string_lossy().into_owned();
+            visitor(&name, &mut entry)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::path::Path;
+
+    use super::{is_archive, split, spool, virtual_path};
+
+    #[test]
+    fn virtual_paths() {
+        let path = virtual_path(Path::new("backup.tar.gz"), "/docs/spec.pdf");
+        assert_eq!(path, "backup.tar.gz!/docs/spec.pdf");
+        assert_eq!(split(&path), Some(("backup.tar.gz", "docs/spec.pdf")));
+        assert_eq!(split("docs/spec.pdf"), None);
+
+        assert!(is_archive(Path::new("Backup.ZIP")));
+        assert!(is_archive(Path::new("logs.tzst")));
+        assert!(!is_archive(Path::new("app.log.gz")));
+    }
+
+    #[test]
+    fn spool_limit() {
+        let file = spool("docs/spec.txt", &mut &b"0123456789"[..], 10).unwrap();
+        assert!(file.path().to_string_lossy().ends_with("spec.txt"));
+        let mut content = String::new();
+        file.reopen().unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "0123456789");
+
+        let err = spool("bomb.txt", &mut &b"0123456789"[..], 9).unwrap_err();
+        assert_eq!(err.to_string(), "size of bomb.txt exceeds 9 bytes");
+    }
+}
@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use tempfile::NamedTempFile;
+use tracing::debug;
+use xz2::read::XzDecoder;
+
+use crate::archive::{spool, MAX_SPOOLED_SIZE};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Compression of the file and the file name inside, e.g. `app.log.gz` => (Gzip, `app.log`).
+    fn of(path: &Path) -> Option<(Self, &str)> {
+        let compression = match path.extension()?.to_str()? {
+            "gz" => Compression::Gzip,
+            "bz2" => Compression::Bzip2,
+            "xz" => Compression::Xz,
+            "zst" => Compression::Zstd,
+            _ => return None,
+        };
+        Some((compression, path.file_stem()?.to_str()?))
+    }
+}
+
+/// Decompresses a compressed single file into a temporary file named after the inner file,
+/// returns `None` if the file is not compressed.
+///
+/// Collectors read files by path, so the content is spooled to disk rather than streamed,
+/// and files expanding to more than [`MAX_SPOOLED_SIZE`] bytes fail.
+pub fn decompress(path: &Path) -> anyhow::Result<Option<NamedTempFile>> {
+    decompress_at_most(path, MAX_SPOOLED_SIZE)
+}
+
+fn decompress_at_most(path: &Path, limit: u64) -> anyhow::Result<Option<NamedTempFile>> {
+    let (compression, name) = match Compression::of(path) {
+        Some(c) => c,
+        None => return Ok(None),
+    };
+    debug!("decompress {:?} file: {:?}", compression, path);
+    let file = File::open(path)?;
+    let mut decoder: Box<dyn Read> = match compression {
+        Compression::Gzip => box MultiGzDecoder::new(file),
+        Compression::Bzip2 => box MultiBzDecoder::new(file),
+        Compression::Xz => box XzDecoder::new(file),
+        Compression::Zstd => box zstd::Decoder::new(file)?,
+    };
+    Ok(Some(spool(name, &mut decoder, limit)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::path::Path;
+
+    use super::{decompress, decompress_at_most};
+
+    const CONTENT: &[u8] = b"GET /index.html 200\nGET /missing 404\n";
+
+    fn compress(name: &str) -> Vec<u8> {
+        let compressed = match Path::new(name).extension().and_then(|e| e.to_str()) {
+            Some("gz") => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(CONTENT).unwrap();
+                encoder.finish()
+            }
+            Some("bz2") => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(CONTENT).unwrap();
+                encoder.finish()
+            }
+            Some("xz") => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(CONTENT).unwrap();
+                encoder.finish()
+            }
+            _ => zstd::encode_all(CONTENT, 0),
+        };
+        compressed.unwrap()
+    }
+
+    #[test]
+    fn decompress_to_inner_file() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["app.log.gz", "app.log.bz2", "app.log.xz", "app.log.zst"] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, compress(name)).unwrap();
+            let file = decompress(&path).unwrap().unwrap();
+            assert!(file.path().to_string_lossy().ends_with("app.log"));
+            let mut content = Vec::new();
+            file.reopen().unwrap().read_to_end(&mut content).unwrap();
+            assert_eq!(content, CONTENT, "{}", name);
+        }
+
+        let plain = dir.path().join("app.log");
+        std::fs::write(&plain, CONTENT).unwrap();
+        assert!(decompress(&plain).unwrap().is_none());
+    }
+
+    #[test]
+    fn decompressed_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bomb.txt.gz");
+        std::fs::write(&path, compress("bomb.txt.gz")).unwrap();
+        let size = CONTENT.len() as u64;
+        assert!(decompress_at_most(&path, size).unwrap().is_some());
+        let err = decompress_at_most(&path, size - 1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("size of bomb.txt exceeds {} bytes", size - 1)
+        );
+    }
+}
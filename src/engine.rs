@@ -1,29 +1,46 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::{metadata, File};
-use std::iter::Iterator;
+use std::io::Read;
+use std::iter::{FromIterator, Iterator};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 
 use anyhow::anyhow;
-use glob::glob;
+use chrono::{DateTime, Local};
+use glob::{glob, Pattern};
+use once_cell::sync::Lazy;
 use rayon::prelude::*;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
 use tantivy::collector::TopDocs;
 use tantivy::directory::MmapDirectory;
-use tantivy::query::{Query, QueryParser, TermQuery};
+use tantivy::query::{Query, QueryParser, RegexQuery, TermQuery};
 use tantivy::schema::*;
 use tantivy::tokenizer::{Language, LowerCaser, Stemmer, TextAnalyzer};
-use tantivy::{doc, Document, Index, ReloadPolicy, SegmentReader, SnippetGenerator, Term};
+use tantivy::{
+    doc, Document, Index, IndexWriter, ReloadPolicy, Searcher, SegmentReader, SnippetGenerator,
+    TantivyError, Term,
+};
+use tracing::warn;
 
 use self::stopwords::StopWordFilter;
-use self::tokenizer::JiebaTokenizer;
-use crate::registry::Registry;
+use self::tokenizer::{ColumnTokenizer, JiebaTokenizer};
+use crate::archive;
+use crate::highlight::Highlighter;
+use crate::registry::{CollectError, Collected, Registry};
 
 mod stopwords;
 mod tokenizer;
 
 const TOKENIZER: &str = "jieba-with-filters";
+const COLUMN_TOKENIZER: &str = "column";
+const COLUMN_FIELD: &str = "column";
 const DEFAULT_HEAP_SIZE: usize = 100_000_000;
 
+/// Clauses of a query like `email:alice` or `name:"Alice Smith"`.
+static CLAUSE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(^|[\s(+-])([\p{L}\p{N}_]+):("[^"]*"|[^\s()"]+)"#).unwrap());
+
 pub struct Engine {
     heap_size: usize,
     registry: Registry,
@@ -36,8 +53,11 @@ struct Fields {
     path: Field,
     collector: Field,
     hash: Field,
+    encoding: Field,
     position: Field,
     line: Field,
+    column: Field,
+    terms: Field,
 }
 
 pub struct Doc<'a> {
@@ -45,8 +65,82 @@ pub struct Doc<'a> {
     doc: Document,
 }
 
+/// A file failed to be indexed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Failure {
+    pub path: String,
+    /// Working directory the path is relative to, empty in logs of older versions.
+    #[serde(default)]
+    pub dir: PathBuf,
+    pub collector: Option<String>,
+    pub error: String,
+    pub time: DateTime<Local>,
+}
+
 pub type Docs<'a> = Box<dyn 'a + Send + Iterator<Item = anyhow::Result<Doc<'a>>>>;
 
+/// A file or an archive selected by path arguments,
+/// entries of archives can be selected by a glob after `!/`, e.g. `backup.zip!/docs/*.pdf`.
+enum Target {
+    File(PathBuf),
+    Archive(PathBuf, Option<Pattern>),
+}
+
+impl Target {
+    fn path(&self) -> &Path {
+        match self {
+            Target::File(p) | Target::Archive(p, _) => p,
+        }
+    }
+}
+
+/// Indexed paths covered by the targets.
+#[derive(Default)]
+struct Scope {
+    files: HashSet<PathBuf>,
+    archives: HashMap<PathBuf, Vec<Option<Pattern>>>,
+}
+
+impl Scope {
+    fn contains(&self, path: &str) -> bool {
+        match archive::split(path) {
+            Some((archive, entry)) => {
+                self.archives
+                    .get(Path::new(archive))
+                    .map_or(false, |patterns| {
+                        patterns
+                            .iter()
+                            .any(|p| p.as_ref().map_or(true, |p| p.matches(entry)))
+                    })
+            }
+            None => self.files.contains(Path::new(path)),
+        }
+    }
+}
+
+impl FromIterator<Target> for Scope {
+    fn from_iter<I: IntoIterator<Item = Target>>(targets: I) -> Self {
+        let mut scope = Scope::default();
+        for target in targets {
+            match target {
+                Target::File(p) => {
+                    scope.files.insert(p);
+                }
+                Target::Archive(p, pattern) => scope.archives.entry(p).or_default().push(pattern),
+            }
+        }
+        scope
+    }
+}
+
+struct Indexer<'a> {
+    registry: &'a Registry,
+    searcher: &'a Searcher,
+    index_writer: &'a RwLock<IndexWriter>,
+    fields: &'a Fields,
+    failures: Mutex<Vec<Failure>>,
+}
+
 impl Doc<'_> {
     pub fn path(&self) -> Option<&str> {
         self.doc.get_first(self.fields.path)?.text()
@@ -60,6 +154,10 @@ impl Doc<'_> {
         self.doc.get_first(self.fields.hash)?.bytes_value()
     }
 
+    pub fn encoding(&self) -> Option<&str> {
+        self.doc.get_first(self.fields.encoding)?.text()
+    }
+
     pub fn lines(&self) -> impl Iterator<Item = (&'_ str, &'_ str)> {
         let positions = self.doc.get_all(self.fields.position);
         let lines = self.doc.get_all(self.fields.line);
@@ -79,24 +177,46 @@ impl Engine {
             .set_tokenizer(TOKENIZER)
             .set_index_option(IndexRecordOption::WithFreqsAndPositions);
         let line_options = TextOptions::default()
-            .set_indexing_options(line_field_indexing)
+            .set_indexing_options(line_field_indexing.clone())
             .set_stored();
+        let terms_options = TextOptions::default().set_indexing_options(line_field_indexing);
+        let column_options = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(COLUMN_TOKENIZER)
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
 
         let mut schema_builder = Schema::builder();
         let path = schema_builder.add_text_field("path", STRING | STORED);
         let collector = schema_builder.add_text_field("collector", STRING | STORED);
         let hash = schema_builder.add_bytes_field("hash", FAST | STORED);
+        let encoding = schema_builder.add_text_field("encoding", STRING | STORED);
         let position = schema_builder.add_text_field("position", STRING | STORED);
         let line = schema_builder.add_text_field("line", line_options);
+        let column = schema_builder.add_text_field(COLUMN_FIELD, column_options);
+        let terms = schema_builder.add_text_field("terms", terms_options);
         let schema = schema_builder.build();
 
         let dir = MmapDirectory::open(&index_dir)?;
-        let index = Index::open_or_create(dir, schema)?;
+        let index = match Index::open_or_create(dir, schema.clone()) {
+            // indexes are rebuilt from files, so just recreate it once the schema changes
+            Err(TantivyError::SchemaError(err)) => {
+                warn!("recreate index in {:?}: {}", index_dir, err);
+                std::fs::remove_dir_all(&index_dir)?;
+                std::fs::create_dir(&index_dir)?;
+                Index::create_in_dir(&index_dir, schema)?
+            }
+            index => index?,
+        };
 
         let tokenizer = TextAnalyzer::from(JiebaTokenizer::default())
             .filter(LowerCaser)
             .filter(StopWordFilter::default())
             .filter(Stemmer::new(Language::English));
+        index.tokenizers().register(
+            COLUMN_TOKENIZER,
+            TextAnalyzer::from(ColumnTokenizer::new(tokenizer.clone())),
+        );
         index.tokenizers().register(TOKENIZER, tokenizer);
         Ok(Self {
             heap_size: heap_size.unwrap_or(DEFAULT_HEAP_SIZE),
@@ -106,67 +226,51 @@ impl Engine {
                 path,
                 collector,
                 hash,
+                encoding,
                 position,
                 line,
+                column,
+                terms,
             },
         })
     }
 
-    pub fn indexing(&mut self, paths: HashSet<&str>) -> anyhow::Result<()> {
-        let index_writer = Arc::new(RwLock::new(self.index.writer(self.heap_size)?));
+    /// Indexes files of paths, failed files are skipped and returned.
+    pub fn indexing(&mut self, paths: HashSet<&str>) -> anyhow::Result<Vec<Failure>> {
+        let index_writer = RwLock::new(self.index.writer(self.heap_size)?);
         let reader = self
             .index
             .reader_builder()
             .reload_policy(ReloadPolicy::Manual)
             .try_into()?;
         let searcher = reader.searcher();
-        Self::glob(paths)
-            .map_with(
-                (
-                    self.registry.clone(),
-                    index_writer.clone(),
-                    self.fields.clone(),
-                ),
-                |(reg, index, fields), p| -> anyhow::Result<()> {
-                    let mut ctx = md5::Context::new();
-                    std::io::copy(&mut File::open(&p)?, &mut ctx)?;
-                    let digest = ctx.compute();
-                    let path_term =
-                        Term::from_field_text(self.fields.path, p.to_string_lossy().as_ref());
-                    let term_query = TermQuery::new(path_term.clone(), IndexRecordOption::Basic);
-                    let top_docs = searcher.search(&term_query, &TopDocs::with_limit(1))?;
-                    if let Some((_score, doc_address)) = top_docs.first() {
-                        let doc = Doc {
-                            fields,
-                            doc: searcher.doc(*doc_address)?,
-                        };
-                        let hash = doc.hash().unwrap();
-                        if hash == digest.as_ref() {
-                            return Ok(());
-                        } else {
-                            index.read().unwrap().delete_term(path_term);
-                        }
-                    }
-
-                    if let Some((co, lines)) = reg.collect(&p) {
-                        let mut doc = doc!(
-                            self.fields.path => p.to_str().ok_or_else(|| anyhow!("invalid path"))?,
-                            self.fields.collector => co,
-                            self.fields.hash => digest.as_ref(),
-                        );
-
-                        for l in lines {
-                            doc.add_text(self.fields.position, l.position);
-                            doc.add_text(self.fields.line, l.line);
-                        }
-                        index_writer.read().unwrap().add_document(doc);
-                    }
-                    Ok(())
-                },
-            )
-            .collect::<anyhow::Result<Vec<_>>>()?;
+        let indexer = Indexer {
+            registry: &self.registry,
+            searcher: &searcher,
+            index_writer: &index_writer,
+            fields: &self.fields,
+            failures: Mutex::default(),
+        };
+        Self::glob(paths).for_each(|target| {
+            let path = target.path().to_string_lossy().into_owned();
+            if let Err(err) = indexer.index(target) {
+                indexer.fail(&path, err);
+            }
+        });
         index_writer.write().unwrap().commit()?;
-        Ok(())
+        let mut failures = indexer.failures.into_inner().unwrap();
+        failures.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(failures)
+    }
+
+    /// Whether the file or archive entry of path is indexed.
+    pub fn is_indexed(&self, path: &str) -> anyhow::Result<bool> {
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        Ok(find(&reader.searcher(), &self.fields, path)?.is_some())
     }
 
     pub fn remove_indexes(&mut self, paths: HashSet<&str>) -> anyhow::Result<()> {
@@ -194,10 +298,29 @@ impl Engine {
         query: &str,
         limit: usize,
         paths: HashSet<&str>,
-    ) -> anyhow::Result<(Docs<'_>, SnippetGenerator)> {
-        let query_parser = QueryParser::for_index(&self.index, vec![self.fields.line]);
-        let query = query_parser.parse_query(query)?;
-        self.query(&query, limit, paths)
+    ) -> anyhow::Result<(Docs<'_>, Highlighter)> {
+        // hidden terms of lines, like sub-tokens of identifiers, are searched along with lines
+        let query_parser =
+            QueryParser::for_index(&self.index, vec![self.fields.line, self.fields.terms]);
+        let schema = self.index.schema();
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let searcher = reader.searcher();
+        // only indexed columns are rewritten, so that `http://host` or `12:30` are kept as is
+        let mut columns = HashSet::new();
+        for caps in CLAUSE.captures_iter(query) {
+            let name = &caps[2];
+            if schema.get_field(name).is_none() && has_column(&searcher, &self.fields, name)? {
+                columns.insert(name.to_string());
+            }
+        }
+        let scoped = query_parser.parse_query(&rewrite_columns(query, &columns, true))?;
+        // lines are highlighted by values of column-scoped clauses
+        let unscoped = query_parser.parse_query(&rewrite_columns(query, &columns, false))?;
+        self.query(&scoped, &unscoped, limit, paths)
     }
 
     pub fn docs(&self, paths: HashSet<&str>) -> anyhow::Result<Docs<'_>> {
@@ -207,22 +330,24 @@ impl Engine {
             .reload_policy(ReloadPolicy::Manual)
             .try_into()?;
         let searcher = reader.searcher();
+        let fields = &self.fields;
         let docs = Self::glob(paths)
-            .map(|p| -> anyhow::Result<_> {
-                let path_term =
-                    Term::from_field_text(self.fields.path, p.to_string_lossy().as_ref());
-                let term_query = TermQuery::new(path_term, IndexRecordOption::Basic);
-                let top_docs = searcher.search(&term_query, &TopDocs::with_limit(1))?;
-                if let Some((_score, doc_address)) = top_docs.first() {
-                    Ok(Some(Doc {
-                        fields: &self.fields,
-                        doc: searcher.doc(*doc_address)?,
-                    }))
-                } else {
-                    Ok(None)
+            .flat_map_iter(|target| {
+                let docs = match target {
+                    Target::File(p) => find(&searcher, fields, &p.to_string_lossy())
+                        .map(|d| d.into_iter().collect()),
+                    Target::Archive(p, pattern) => {
+                        find_entries(&searcher, fields, &p, pattern.as_ref())
+                    }
+                };
+                match docs {
+                    Ok(docs) => docs
+                        .into_iter()
+                        .map(|doc| Ok(Doc { fields, doc }))
+                        .collect(),
+                    Err(err) => vec![Err(err)],
                 }
             })
-            .filter_map(Result::transpose)
             .collect::<Vec<_>>();
         Ok(box docs.into_iter())
     }
@@ -230,18 +355,27 @@ impl Engine {
     fn query(
         &self,
         query: &dyn Query,
+        snippet_query: &dyn Query,
         limit: usize,
         paths: HashSet<&str>,
-    ) -> anyhow::Result<(Docs<'_>, SnippetGenerator)> {
+    ) -> anyhow::Result<(Docs<'_>, Highlighter)> {
         let reader = self
             .index
             .reader_builder()
             .reload_policy(ReloadPolicy::Manual)
             .try_into()?;
         let searcher = Arc::new(reader.searcher());
-        let snippet_generator = SnippetGenerator::create(&searcher, query, self.fields.line)?;
-        let path_set = Arc::new(Self::glob(paths).collect::<HashSet<PathBuf>>());
-        let path_set_cpy = path_set.clone();
+        let highlighter = Highlighter::new(
+            SnippetGenerator::create(&searcher, snippet_query, self.fields.line)?,
+            SnippetGenerator::create(&searcher, snippet_query, self.fields.terms)?,
+        );
+        let scope = Arc::new(
+            Self::glob(paths)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .collect::<Scope>(),
+        );
+        let scope_cpy = scope.clone();
         let fields = Arc::new(self.fields.clone());
         let top_docs = searcher.search(
             query,
@@ -249,7 +383,7 @@ impl Engine {
                 let store_reader = segment_reader
                     .get_store_reader()
                     .expect("tweaking score needs store reader");
-                let p_set = path_set_cpy.clone();
+                let scope = scope_cpy.clone();
                 let fields = fields.clone();
                 move |doc_id, original_score| {
                     let doc = Doc {
@@ -259,8 +393,7 @@ impl Engine {
                             .expect("get document from store reader"),
                     };
 
-                    let path: &Path = doc.path().unwrap().as_ref();
-                    if p_set.contains(path) {
+                    if scope.contains(doc.path().unwrap()) {
                         original_score
                     } else {
                         f32::MIN
@@ -279,31 +412,368 @@ impl Engine {
             })
             .filter_map(move |d| {
                 if let Ok(ref doc) = d {
-                    let path: &Path = doc.path()?.as_ref();
-                    if !path_set.contains(path) {
+                    if !scope.contains(doc.path()?) {
                         return None;
                     }
                 }
                 Some(d)
             });
-        Ok((box docs, snippet_generator))
+        Ok((box docs, highlighter))
     }
 
-    fn glob(paths: HashSet<&'_ str>) -> impl '_ + ParallelIterator<Item = PathBuf> {
+    fn glob(paths: HashSet<&'_ str>) -> impl '_ + ParallelIterator<Item = Target> {
         paths
             .into_iter()
-            .filter_map(|p| glob(p).ok())
+            .filter_map(|p| {
+                let (p, pattern) = match archive::split(p) {
+                    Some((archive, entries)) => (archive, Some(Pattern::new(entries).ok()?)),
+                    None => (p, None),
+                };
+                Some(glob(p).ok()?.map(move |p| (p, pattern.clone())))
+            })
             .flatten()
             .par_bridge()
-            .filter_map(|p| p.ok())
-            .filter_map(|p| {
+            .filter_map(|(p, pattern)| Some((p.ok()?, pattern)))
+            .filter_map(|(p, pattern)| {
                 let meta = metadata(&p).ok()?;
                 if meta.is_file() || meta.is_symlink() {
-                    Some(p)
+                    Some((p, pattern))
                 } else {
                     None
                 }
             })
-            .filter(|meta| meta.is_file())
+            .filter(|(p, _)| p.is_file())
+            .filter_map(|(p, pattern)| {
+                if archive::is_archive(&p) {
+                    Some(Target::Archive(p, pattern))
+                } else if pattern.is_none() {
+                    Some(Target::File(p))
+                } else {
+                    None
+                }
+            })
+    }
+}
+
+impl Indexer<'_> {
+    fn index(&self, target: Target) -> anyhow::Result<()> {
+        let p = target.path();
+        let path = p.to_str().ok_or_else(|| anyhow!("invalid path"))?;
+        match target {
+            Target::File(ref p) => {
+                let digest = self.digest(File::open(p)?)?;
+                if !self.is_outdated(path, digest)? {
+                    return Ok(());
+                }
+                self.add(path, digest, self.registry.collect(p)?)
+            }
+            Target::Archive(ref p, ref pattern) => {
+                // entries no longer in the archive should be removed
+                let mut stale = match pattern {
+                    Some(_) => HashSet::new(),
+                    None => find_entries(self.searcher, self.fields, p, None)?
+                        .iter()
+                        .filter_map(|doc| {
+                            Some(doc.get_first(self.fields.path)?.text()?.to_string())
+                        })
+                        .collect(),
+                };
+                archive::visit(p, |name, reader| {
+                    if !pattern.as_ref().map_or(true, |p| p.matches(name)) {
+                        return Ok(());
+                    }
+                    let path = archive::virtual_path(p, name);
+                    stale.remove(&path);
+                    // failed entries shouldn't abort the rest of the archive
+                    if let Err(err) = self.index_entry(&path, name, reader) {
+                        self.fail(&path, err);
+                    }
+                    Ok(())
+                })?;
+                for path in stale {
+                    let path_term = Term::from_field_text(self.fields.path, &path);
+                    self.index_writer.read().unwrap().delete_term(path_term);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Indexes an entry spooled to a temporary file, which collectors read by path.
+    fn index_entry(&self, path: &str, name: &str, reader: &mut dyn Read) -> anyhow::Result<()> {
+        let file = archive::spool(name, reader, archive::MAX_SPOOLED_SIZE)?;
+        let digest = self.digest(file.reopen()?)?;
+        if !self.is_outdated(path, digest)? {
+            return Ok(());
+        }
+        self.add(path, digest, self.registry.collect_as(file.path(), path)?)
+    }
+
+    fn fail(&self, path: &str, err: anyhow::Error) {
+        warn!("fail to index {}: {:#}", path, err);
+        self.failures.lock().unwrap().push(Failure {
+            path: path.to_string(),
+            dir: std::env::current_dir().unwrap_or_default(),
+            collector: err
+                .downcast_ref::<CollectError>()
+                .and_then(|e| e.collector())
+                .map(ToString::to_string),
+            error: format!("{:#}", err),
+            time: Local::now(),
+        });
+    }
+
+    fn digest(&self, mut content: impl Read) -> anyhow::Result<md5::Digest> {
+        let mut ctx = md5::Context::new();
+        std::io::copy(&mut content, &mut ctx)?;
+        Ok(ctx.compute())
+    }
+
+    /// Digest of the content and options of the collector, so that files are indexed again
+    /// once options of their collector like `--sheet-rows` change.
+    fn collected_digest(&self, digest: md5::Digest, collector: &str) -> md5::Digest {
+        let mut ctx = md5::Context::new();
+        ctx.consume(digest.as_ref());
+        if let Some(collector) = self.registry.get(collector) {
+            ctx.consume(collector.config_digest());
+        }
+        ctx.compute()
+    }
+
+    /// Checks whether the indexed document of path is missing or outdated,
+    /// outdated documents are deleted.
+    fn is_outdated(&self, path: &str, digest: md5::Digest) -> anyhow::Result<bool> {
+        if let Some(doc) = find(self.searcher, self.fields, path)? {
+            let doc = Doc {
+                fields: self.fields,
+                doc,
+            };
+            let hash = doc.hash().unwrap();
+            let digest = match doc.collector() {
+                Some(collector) => self.collected_digest(digest, collector),
+                None => digest,
+            };
+            if hash == digest.as_ref() {
+                return Ok(false);
+            } else {
+                let path_term = Term::from_field_text(self.fields.path, path);
+                self.index_writer.read().unwrap().delete_term(path_term);
+            }
+        }
+        Ok(true)
+    }
+
+    fn add(
+        &self,
+        path: &str,
+        digest: md5::Digest,
+        collected: Option<Collected>,
+    ) -> anyhow::Result<()> {
+        if let Some(collected) = collected {
+            let hash = self.collected_digest(digest, collected.collector);
+            let mut doc = doc!(
+                self.fields.path => path,
+                self.fields.collector => collected.collector,
+                self.fields.hash => hash.as_ref(),
+            );
+            if let Some(encoding) = collected.encoding {
+                doc.add_text(self.fields.encoding, encoding);
+            }
+
+            for l in collected.lines {
+                for (name, value) in &l.columns {
+                    doc.add_text(
+                        self.fields.column,
+                        format!("{}:{}", column_name(name), value),
+                    );
+                }
+                if !l.terms.is_empty() {
+                    doc.add_text(self.fields.terms, l.terms.join(" "));
+                }
+                doc.add_text(self.fields.position, l.position);
+                doc.add_text(self.fields.line, l.line);
+            }
+            self.index_writer.read().unwrap().add_document(doc);
+        }
+        Ok(())
+    }
+}
+
+/// Rewrites clauses of `columns` like `email:alice` to terms of record columns
+/// (`column:"email:alice"`), or to their values if not `scoped`.
+fn rewrite_columns(query: &str, columns: &HashSet<String>, scoped: bool) -> String {
+    CLAUSE
+        .replace_all(query, |caps: &Captures| {
+            let (prefix, name, value) = (&caps[1], &caps[2], caps[3].trim_matches('"'));
+            if !columns.contains(name) {
+                caps[0].to_string()
+            } else if scoped {
+                format!(
+                    r#"{}{}:"{}:{}""#,
+                    prefix,
+                    COLUMN_FIELD,
+                    column_name(name),
+                    value
+                )
+            } else {
+                format!(r#"{}"{}""#, prefix, value)
+            }
+        })
+        .into_owned()
+}
+
+/// Whether values of the column are indexed, i.e. there are terms like `email:...`.
+fn has_column(searcher: &Searcher, fields: &Fields, name: &str) -> anyhow::Result<bool> {
+    let prefix = format!("{}:", column_name(name));
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader.inverted_index(fields.column)?;
+        let mut terms = inverted_index
+            .terms()
+            .range()
+            .ge(prefix.as_bytes())
+            .into_stream()?;
+        if terms.advance() && terms.key().starts_with(prefix.as_bytes()) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Normalized name of a column in terms, e.g. `E-mail Address` => `e_mail_address`.
+fn column_name(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn find(searcher: &Searcher, fields: &Fields, path: &str) -> anyhow::Result<Option<Document>> {
+    let path_term = Term::from_field_text(fields.path, path);
+    let term_query = TermQuery::new(path_term, IndexRecordOption::Basic);
+    let top_docs = searcher.search(&term_query, &TopDocs::with_limit(1))?;
+    match top_docs.first() {
+        Some((_score, doc_address)) => Ok(Some(searcher.doc(*doc_address)?)),
+        None => Ok(None),
+    }
+}
+
+/// Finds indexed entries of the archive, optionally filtered by a pattern of entry names.
+fn find_entries(
+    searcher: &Searcher,
+    fields: &Fields,
+    path: &Path,
+    pattern: Option<&Pattern>,
+) -> anyhow::Result<Vec<Document>> {
+    let prefix = archive::virtual_path(path, "");
+    let query = RegexQuery::from_pattern(&format!("{}.*", regex::escape(&prefix)), fields.path)?;
+    let limit = (searcher.num_docs() as usize).max(1);
+    let mut docs = Vec::new();
+    for (_score, doc_address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+        let doc = searcher.doc(doc_address)?;
+        let entry = doc
+            .get_first(fields.path)
+            .and_then(|p| p.text())
+            .and_then(|p| p.strip_prefix(&prefix));
+        if let Some(entry) = entry {
+            if pattern.map_or(true, |p| p.matches(entry)) {
+                docs.push(doc);
+            }
+        }
+    }
+    Ok(docs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    use glob::Pattern;
+    use sgrep_collector::SourceCollector;
+
+    use super::{Engine, Scope, Target};
+    use crate::registry::Registry;
+
+    #[test]
+    fn scope_of_targets() {
+        let scope = [
+            Target::File(PathBuf::from("notes.txt")),
+            Target::Archive(PathBuf::from("backup.zip"), Pattern::new("docs/*.pdf").ok()),
+            Target::Archive(PathBuf::from("backup.zip"), Pattern::new("*.md").ok()),
+            Target::Archive(PathBuf::from("logs.tar"), None),
+        ]
+        .into_iter()
+        .collect::<Scope>();
+        assert!(scope.contains("notes.txt"));
+        assert!(!scope.contains("todo.txt"));
+        assert!(scope.contains("backup.zip!/docs/spec.pdf"));
+        assert!(scope.contains("backup.zip!/README.md"));
+        assert!(!scope.contains("backup.zip!/src/main.rs"));
+        assert!(scope.contains("logs.tar!/var/log/app.log"));
+        // archives are not files
+        assert!(!scope.contains("backup.zip"));
+    }
+
+    #[test]
+    fn highlight_terms_only_hits() {
+        let dir = tempfile::tempdir().unwrap();
+        let index_dir = dir.path().join("index");
+        std::fs::create_dir(&index_dir).unwrap();
+        let source = dir.path().join("client.rs");
+        std::fs::write(
+            &source,
+            "// HTTP client\nfn parseHTTPResponse(body: &str) {}\n",
+        )
+        .unwrap();
+        let path = source.to_str().unwrap();
+
+        let registry = Registry::builder()
+            .register(box SourceCollector {
+                split_identifiers: true,
+                comments: false,
+            })
+            .build()
+            .unwrap();
+        let mut engine = Engine::init(index_dir, registry, None).unwrap();
+        assert!(engine.indexing(HashSet::from([path])).unwrap().is_empty());
+
+        colored::control::set_override(false);
+        let (docs, highlighter) = engine.search("response", 5, HashSet::from([path])).unwrap();
+        let docs = docs.collect::<anyhow::Result<Vec<_>>>().unwrap();
+        assert_eq!(docs.len(), 1);
+        // `response` is only a sub-token of `parseHTTPResponse`, not a term of the line
+        let lines = docs[0]
+            .lines()
+            .filter_map(|(_, line)| highlighter.highlight(line))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec!["fn parseHTTPResponse(body: &str) {}"]);
+    }
+
+    #[test]
+    fn index_again_once_collector_options_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let index_dir = dir.path().join("index");
+        std::fs::create_dir(&index_dir).unwrap();
+        let source = dir.path().join("client.rs");
+        std::fs::write(&source, "fn parseHTTPResponse(body: &str) {}\n").unwrap();
+        let path = source.to_str().unwrap();
+
+        let hits = |split_identifiers| {
+            let registry = Registry::builder()
+                .register(box SourceCollector {
+                    split_identifiers,
+                    comments: false,
+                })
+                .build()
+                .unwrap();
+            let mut engine = Engine::init(index_dir.clone(), registry, None).unwrap();
+            assert!(engine.indexing(HashSet::from([path])).unwrap().is_empty());
+            let (docs, _) = engine.search("response", 5, HashSet::from([path])).unwrap();
+            docs.count()
+        };
+        assert_eq!(hits(false), 0);
+        // the unchanged file is collected again with sub-tokens of identifiers
+        assert_eq!(hits(true), 1);
     }
 }
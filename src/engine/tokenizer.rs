@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use jieba_rs::Jieba;
-use tantivy::tokenizer::{BoxTokenStream, Token, TokenStream, Tokenizer};
+use tantivy::tokenizer::{BoxTokenStream, TextAnalyzer, Token, TokenStream, Tokenizer};
 
 #[derive(Clone)]
 pub struct JiebaTokenizer {
@@ -67,6 +67,37 @@ impl Tokenizer for JiebaTokenizer {
     }
 }
 
+/// Tokenizes `name:value` of record columns to tokens like `name:token`,
+/// so that a token of a column doesn't match the same token of other columns.
+#[derive(Clone)]
+pub struct ColumnTokenizer {
+    analyzer: TextAnalyzer,
+}
+
+impl ColumnTokenizer {
+    pub fn new(analyzer: TextAnalyzer) -> Self {
+        Self { analyzer }
+    }
+}
+
+impl Tokenizer for ColumnTokenizer {
+    fn token_stream<'a>(&self, text: &'a str) -> BoxTokenStream<'a> {
+        let (name, value) = text.split_once(':').unwrap_or(("", text));
+        let offset = text.len() - value.len();
+        let mut token_stream = self.analyzer.token_stream(value);
+        let mut tokens = Vec::new();
+        while let Some(token) = token_stream.next() {
+            tokens.push(Token {
+                offset_from: token.offset_from + offset,
+                offset_to: token.offset_to + offset,
+                text: format!("{}:{}", name, token.text),
+                ..token.clone()
+            });
+        }
+        BoxTokenStream::from(JiebaTokenStream { tokens, index: 0 })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -125,4 +156,21 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn column_tokens() {
+        use tantivy::tokenizer::*;
+        let analyzer = TextAnalyzer::from(super::JiebaTokenizer::default()).filter(LowerCaser);
+        let tokenizer = super::ColumnTokenizer::new(analyzer);
+        let mut token_stream = tokenizer.token_stream("dept:Research 北京");
+        let mut tokens = Vec::new();
+        while let Some(token) = token_stream.next() {
+            tokens.push(token.clone());
+        }
+        let texts = tokens.iter().map(|t| t.text.as_str()).collect::<Vec<_>>();
+        assert!(texts.contains(&"dept:research"));
+        assert!(texts.contains(&"dept:北京"));
+        let beijing = tokens.iter().find(|t| t.text == "dept:北京").unwrap();
+        assert_eq!(beijing.offset_from, "dept:Research ".len());
+    }
 }
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::fs::{try_exists, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+use glob::Pattern;
+
+use crate::archive;
+use crate::engine::{Engine, Failure};
+
+/// Files failed to be indexed, persisted across runs in the meta dir.
+pub struct FailureLog {
+    path: PathBuf,
+    /// Failures by absolute paths, as runs in different directories record relative ones.
+    failures: BTreeMap<String, Failure>,
+}
+
+impl FailureLog {
+    pub fn open(path: PathBuf) -> anyhow::Result<Self> {
+        let failures = if try_exists(&path)? {
+            serde_json::from_reader::<_, Vec<Failure>>(BufReader::new(File::open(&path)?))?
+                .into_iter()
+                .map(|f| (absolute(&f), f))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { path, failures })
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &Failure> {
+        self.failures.values()
+    }
+
+    /// Absolute paths of failed files escaped as glob patterns.
+    pub fn patterns(&self) -> Vec<String> {
+        self.failures
+            .keys()
+            .map(|path| match archive::split(path) {
+                Some((archive, entry)) => format!(
+                    "{}{}{}",
+                    Pattern::escape(archive),
+                    archive::SEPARATOR,
+                    Pattern::escape(entry)
+                ),
+                None => Pattern::escape(path),
+            })
+            .collect()
+    }
+
+    /// Records failures of an indexing run,
+    /// files indexed since or no longer existing are dropped.
+    pub fn update(&mut self, engine: &Engine, failures: Vec<Failure>) -> anyhow::Result<()> {
+        for failure in failures {
+            self.failures.insert(absolute(&failure), failure);
+        }
+        let current_dir = std::env::current_dir()?;
+        let mut resolved = Vec::new();
+        for (path, failure) in &self.failures {
+            let file = archive::split(path).map_or(path.as_str(), |(archive, _)| archive);
+            // documents are indexed by paths as given, relative ones only match in their directory
+            let indexed = engine.is_indexed(path)?
+                || (failure.dir == current_dir && engine.is_indexed(&failure.path)?);
+            if !try_exists(file)? || indexed {
+                resolved.push(path.clone());
+            }
+        }
+        for path in resolved {
+            self.failures.remove(&path);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let writer = BufWriter::new(File::create(&self.path)?);
+        serde_json::to_writer_pretty(writer, &self.failures.values().collect::<Vec<_>>())?;
+        Ok(())
+    }
+}
+
+/// Path of the failure resolved against the directory it was recorded in.
+fn absolute(failure: &Failure) -> String {
+    match archive::split(&failure.path) {
+        Some((archive, entry)) => archive::virtual_path(&failure.dir.join(archive), entry),
+        None => failure
+            .dir
+            .join(&failure.path)
+            .to_string_lossy()
+            .into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::Path;
+
+    use chrono::Local;
+    use glob::Pattern;
+    use sgrep_collector::UTF8Collector;
+
+    use super::FailureLog;
+    use crate::engine::{Engine, Failure};
+    use crate::registry::Registry;
+
+    fn failure(dir: &Path) -> Failure {
+        Failure {
+            path: "notes.txt".to_string(),
+            dir: dir.to_path_buf(),
+            collector: Some("utf8".to_string()),
+            error: "interrupted".to_string(),
+            time: Local::now(),
+        }
+    }
+
+    #[test]
+    fn retry_failures_of_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let index_dir = dir.path().join("index");
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        for d in [&index_dir, &a, &b] {
+            std::fs::create_dir(d).unwrap();
+        }
+        std::fs::write(a.join("notes.txt"), "first\n").unwrap();
+        std::fs::write(b.join("notes.txt"), "second\n").unwrap();
+
+        let registry = Registry::builder()
+            .register(box UTF8Collector)
+            .build()
+            .unwrap();
+        let mut engine = Engine::init(index_dir, registry, None).unwrap();
+        let log_path = dir.path().join("failures.json");
+        let mut log = FailureLog::open(log_path.clone()).unwrap();
+        // the same relative path recorded by runs in two directories
+        log.update(&engine, vec![failure(&a), failure(&b)]).unwrap();
+
+        let mut log = FailureLog::open(log_path).unwrap();
+        let patterns = log.patterns();
+        let expected = [a.join("notes.txt"), b.join("notes.txt")]
+            .map(|p| Pattern::escape(p.to_str().unwrap()));
+        assert_eq!(patterns, expected);
+
+        let failures = engine
+            .indexing(patterns.iter().map(String::as_str).collect::<HashSet<_>>())
+            .unwrap();
+        assert!(failures.is_empty());
+        log.update(&engine, failures).unwrap();
+        assert_eq!(log.failures().count(), 0);
+    }
+}
@@ -5,10 +5,9 @@ use clap::Args;
 use colored::Colorize;
 use rayon::prelude::*;
 use regex::Regex;
-use sgrep_collector::all_collectors;
 
-use crate::registry::Registry;
-use crate::{Command, Engine};
+use crate::index::CollectOptions;
+use crate::{index, Command, Engine};
 
 /// Precisely match words by regex
 #[derive(Debug, PartialEq, Args)]
@@ -25,8 +24,12 @@ pub struct Grep {
     pattern: String,
 
     /// Paths to index and match, support [glob](https://github.com/rust-lang-nursery/glob)
+    /// and entries of archives like `backup.zip!/docs/*.pdf`
     #[clap(default_value = "*")]
     paths: Vec<String>,
+
+    #[clap(flatten)]
+    options: CollectOptions,
 }
 
 // // Not work now
@@ -43,13 +46,10 @@ impl Command for Grep {
         } else {
             Regex::new(&format!(r"(?i){}", self.pattern))?
         };
-        let registry = Registry::builder()
-            .register_list(all_collectors())
-            .build()?;
-        let mut engine = Engine::init(index_dir, registry, None)?;
+        let mut engine = Engine::init(index_dir, self.options.registry()?, None)?;
         let paths: HashSet<&str> = self.paths.iter().map(|s| s.as_str()).collect();
         if self.indexing {
-            engine.indexing(paths.clone())?;
+            index::indexing(&mut engine, paths.clone())?;
         }
         let docs = engine
             .docs(paths)?
@@ -76,13 +76,23 @@ impl Command for Grep {
                     Some((
                         doc.path().unwrap().to_string(),
                         doc.collector().unwrap().to_string(),
+                        doc.encoding().map(ToString::to_string),
                         lines,
                     ))
                 }
             })
             .collect::<Vec<_>>();
-        for (path, collector, lines) in docs {
-            println!("{}({})", path.purple(), collector.yellow().italic());
+        for (path, collector, encoding, lines) in docs {
+            // UTF-8 is the norm, only legacy encodings are worth noting
+            match encoding.filter(|e| e != "UTF-8") {
+                Some(encoding) => println!(
+                    "{}({}, {})",
+                    path.purple(),
+                    collector.yellow().italic(),
+                    encoding.cyan()
+                ),
+                None => println!("{}({})", path.purple(), collector.yellow().italic()),
+            }
             for (p, l) in lines {
                 println!("{}:{}", p.green(), l);
             }
@@ -1,7 +1,54 @@
 use colored::Colorize;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sgrep_collector::split_identifiers;
 use tantivy::SnippetGenerator;
 
-pub fn highlight(generator: &SnippetGenerator, text: &str) -> Option<String> {
+static IDENTIFIER: Lazy<Regex> = Lazy::new(|| Regex::new(r"[A-Za-z0-9_]+").unwrap());
+
+/// Highlights lines by terms of the query, lines matched only by hidden terms like
+/// `response` of `parseHTTPResponse` are highlighted by the whole identifiers.
+pub struct Highlighter {
+    line: SnippetGenerator,
+    terms: SnippetGenerator,
+}
+
+impl Highlighter {
+    pub fn new(line: SnippetGenerator, terms: SnippetGenerator) -> Self {
+        Self { line, terms }
+    }
+
+    pub fn highlight(&self, text: &str) -> Option<String> {
+        highlight(&self.line, text).or_else(|| self.highlight_identifiers(text))
+    }
+
+    fn highlight_identifiers(&self, text: &str) -> Option<String> {
+        let mut result = String::with_capacity(text.len());
+        let mut start_from = 0;
+        for identifier in IDENTIFIER.find_iter(text) {
+            let terms = split_identifiers(identifier.as_str());
+            if terms.is_empty()
+                || self
+                    .terms
+                    .snippet(&terms.join(" "))
+                    .highlighted()
+                    .is_empty()
+            {
+                continue;
+            }
+            result.push_str(&text[start_from..identifier.start()]);
+            result.push_str(&format!("{}", identifier.as_str().red().bold()));
+            start_from = identifier.end();
+        }
+        if start_from == 0 {
+            return None;
+        }
+        result.push_str(&text[start_from..]);
+        Some(result)
+    }
+}
+
+fn highlight(generator: &SnippetGenerator, text: &str) -> Option<String> {
     let snippet = generator.snippet(text);
     if snippet.fragments().is_empty() {
         return None;
@@ -1,10 +1,15 @@
+use std::collections::HashSet;
 use std::path::PathBuf;
 
 use clap::Args;
-use sgrep_collector::all_collectors;
+use colored::Colorize;
+use sgrep_collector::{all_collectors, Config};
 
+use crate::engine::Failure;
+use crate::failure::FailureLog;
+use crate::password::Passwords;
 use crate::registry::Registry;
-use crate::{Command, Engine};
+use crate::{failures_file, passwords_file, root_dir, Command, Engine};
 
 /// Manage indexes
 #[derive(Debug, PartialEq, Args)]
@@ -17,23 +22,106 @@ pub struct Index {
     #[clap(short = 'D', long)]
     delete_all: bool,
 
+    /// List files failed to be indexed
+    #[clap(short, long)]
+    errors: bool,
+
+    /// Index files failed to be indexed again
+    #[clap(short, long)]
+    retry: bool,
+
     /// Paths to index and match, support [glob](https://github.com/rust-lang-nursery/glob)
+    /// and entries of archives like `backup.zip!/docs/*.pdf`
     #[clap(default_value = "*")]
     paths: Vec<String>,
+
+    #[clap(flatten)]
+    options: CollectOptions,
+}
+
+/// Options of collecting files, shared by commands indexing files.
+#[derive(Debug, PartialEq, Args)]
+pub struct CollectOptions {
+    /// Index rows of sheets joined with the header row, like `Name: Alice | Dept: R&D`
+    #[clap(long)]
+    sheet_rows: bool,
+
+    /// Index code blocks of Markdown files
+    #[clap(long)]
+    markdown_code: bool,
+
+    /// Index sub-tokens of identifiers in source files, e.g. `index_files` by `files`
+    #[clap(long)]
+    source_split_identifiers: bool,
+
+    /// Index only comments and docstrings of source files
+    #[clap(long)]
+    source_comments: bool,
+}
+
+impl CollectOptions {
+    pub fn registry(&self) -> anyhow::Result<Registry> {
+        let config = Config {
+            sheet_rows: self.sheet_rows,
+            markdown_code: self.markdown_code,
+            source_split_identifiers: self.source_split_identifiers,
+            source_comments: self.source_comments,
+        };
+        Registry::builder()
+            .register_list(all_collectors(&config))
+            .passwords(Passwords::load(&passwords_file(&root_dir()?))?)
+            .build()
+    }
 }
 
 impl Command for Index {
     fn run(&self, index_dir: PathBuf) -> anyhow::Result<()> {
-        let registry = Registry::builder()
-            .register_list(all_collectors())
-            .build()?;
-        let mut engine = Engine::init(index_dir, registry, None)?;
+        let mut engine = Engine::init(index_dir, self.options.registry()?, None)?;
         if self.delete_all {
             engine.remove_all_indexes()
         } else if self.delete {
             engine.remove_indexes(self.paths.iter().map(|s| s.as_str()).collect())
+        } else if self.errors {
+            for failure in FailureLog::open(failures_file(&root_dir()?))?.failures() {
+                println!(
+                    "{} {}",
+                    failure.time.format("%Y-%m-%d %H:%M:%S").to_string().blue(),
+                    display(failure)
+                );
+            }
+            Ok(())
+        } else if self.retry {
+            let patterns = FailureLog::open(failures_file(&root_dir()?))?.patterns();
+            indexing(&mut engine, patterns.iter().map(|s| s.as_str()).collect())
         } else {
-            engine.indexing(self.paths.iter().map(|s| s.as_str()).collect())
+            indexing(&mut engine, self.paths.iter().map(|s| s.as_str()).collect())
+        }
+    }
+}
+
+/// Indexes files of paths, failed files are reported and recorded for `sgrep index --errors`.
+pub fn indexing(engine: &mut Engine, paths: HashSet<&str>) -> anyhow::Result<()> {
+    let failures = engine.indexing(paths)?;
+    if !failures.is_empty() {
+        eprintln!(
+            "{}",
+            format!("{} files failed to index:", failures.len()).red()
+        );
+        for failure in &failures {
+            eprintln!("{}", display(failure));
         }
     }
+    FailureLog::open(failures_file(&root_dir()?))?.update(engine, failures)
+}
+
+fn display(failure: &Failure) -> String {
+    match failure.collector {
+        Some(ref collector) => format!(
+            "{}({}): {}",
+            failure.path.purple(),
+            collector.yellow().italic(),
+            failure.error
+        ),
+        None => format!("{}: {}", failure.path.purple(), failure.error),
+    }
 }
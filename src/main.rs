@@ -13,15 +13,23 @@ use tracing_subscriber::filter::LevelFilter;
 
 use self::engine::Engine;
 
+mod archive;
+mod compression;
 mod engine;
+mod failure;
 mod grep;
 mod highlight;
 pub mod index;
+mod password;
 pub mod registry;
 mod search;
 
 const META_DIR: &str = "sgrep";
 const INDEX_DIR: &str = "sgrep/index";
+const FAILURES_FILE: &str = "sgrep/failures.json";
+const PASSWORDS_FILE: &str = "sgrep/passwords";
+/// Overrides the password file in the meta dir.
+const PASSWORD_FILE_ENV: &str = "SGREP_PASSWORD_FILE";
 
 /// Super Grep, search words in everything
 #[derive(Parser, Debug)]
@@ -114,3 +122,11 @@ fn index_dir(root: &Path) -> PathBuf {
 fn meta_dir(root: &Path) -> PathBuf {
     root.join(META_DIR)
 }
+
+fn failures_file(root: &Path) -> PathBuf {
+    root.join(FAILURES_FILE)
+}
+
+fn passwords_file(root: &Path) -> PathBuf {
+    std::env::var_os(PASSWORD_FILE_ENV).map_or_else(|| root.join(PASSWORDS_FILE), PathBuf::from)
+}
@@ -0,0 +1,109 @@
+use std::env::current_dir;
+use std::fs::{read_to_string, try_exists};
+use std::path::Path;
+
+use glob::Pattern;
+
+/// Passwords of encrypted files, one rule per line of the password file:
+///
+/// ```text
+/// # glob and password separated by the first `=`, globs without `/` match file names
+/// ~/finance/**/*.xlsx = s3cret
+/// *.pdf               = hunter2
+/// # a single password is tried on all encrypted files, it may contain spaces but not `=`
+/// correct horse battery staple
+/// # passwords with `=` are tried on all encrypted files by the glob `*`
+/// * = pa=ss
+/// # double quotes keep leading and trailing spaces, and `=` of single passwords
+/// *.docx = " padded "
+/// "a=b"
+/// ```
+#[derive(Debug, Default)]
+pub struct Passwords {
+    rules: Vec<(Option<Pattern>, String)>,
+}
+
+impl Passwords {
+    /// Loads passwords from the file, no passwords if it doesn't exist.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !try_exists(path)? {
+            return Ok(Self::default());
+        }
+        Self::parse(&read_to_string(path)?)
+    }
+
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let mut rules = Vec::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=').filter(|_| !line.starts_with('"')) {
+                Some((glob, password)) => rules.push((
+                    Some(Pattern::new(&expand_home(glob.trim()))?),
+                    unquote(password.trim_start()).to_string(),
+                )),
+                None => rules.push((None, unquote(line).to_string())),
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    /// Passwords to try on the file in the order of rules,
+    /// relative paths are also matched in absolute form.
+    pub fn candidates<'a>(&'a self, path: &'a str) -> impl 'a + Iterator<Item = &'a str> {
+        let name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path);
+        let absolute = current_dir().map(|dir| dir.join(path)).ok();
+        self.rules
+            .iter()
+            .filter(move |(pattern, _)| match pattern {
+                Some(pattern) if !pattern.as_str().contains('/') => pattern.matches(name),
+                Some(pattern) => {
+                    pattern.matches(path)
+                        || absolute.as_ref().map_or(false, |p| pattern.matches_path(p))
+                }
+                None => true,
+            })
+            .map(|(_, password)| password.as_str())
+    }
+}
+
+fn unquote(password: &str) -> &str {
+    password
+        .strip_prefix('"')
+        .and_then(|p| p.strip_suffix('"'))
+        .unwrap_or(password)
+}
+
+fn expand_home(glob: &str) -> String {
+    match (glob.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => {
+            format!("{}/{}", Pattern::escape(&home.to_string_lossy()), rest)
+        }
+        _ => glob.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Passwords;
+
+    #[test]
+    fn parse_rules() {
+        let passwords = Passwords::parse(
+            "# comment\n*.pdf = hunter2\n*.docx = \" padded \"\n* = pa=ss \ncorrect horse\n\"a=b\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            passwords.candidates("report.pdf").collect::<Vec<_>>(),
+            vec!["hunter2", "pa=ss", "correct horse", "a=b"]
+        );
+        assert_eq!(
+            passwords.candidates("docs/memo.docx").collect::<Vec<_>>(),
+            vec![" padded ", "pa=ss", "correct horse", "a=b"]
+        );
+    }
+}
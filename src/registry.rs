@@ -1,18 +1,62 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
 use std::iter::IntoIterator;
 use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::anyhow;
-use sgrep_collector::{Collector, Line};
+use sgrep_collector::{sample, sniff, Collector, Encrypted, Line};
+use tracing::warn;
 
+use crate::compression::decompress;
+use crate::password::Passwords;
+
+/// Lines collected from a file and how they were collected.
+#[derive(Debug)]
+pub struct Collected {
+    pub collector: &'static str,
+    pub encoding: Option<&'static str>,
+    pub lines: Vec<Line>,
+}
+
+/// Errors of all collectors accepting a file, in dispatching order.
+#[derive(Debug)]
+pub struct CollectError {
+    pub failures: Vec<(&'static str, anyhow::Error)>,
+}
+
+impl CollectError {
+    /// Name of the first collector failed.
+    pub fn collector(&self) -> Option<&'static str> {
+        self.failures.first().map(|(name, _)| *name)
+    }
+}
+
+impl Display for CollectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, err)) in self.failures.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}: {:#}", name, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CollectError {}
+
+/// Collectors in dispatching order: registration order, then fallback collectors.
 #[derive(Clone)]
 pub struct Registry {
-    collectors: Arc<HashMap<&'static str, Box<dyn Collector>>>,
+    collectors: Arc<Vec<Box<dyn Collector>>>,
+    names: Arc<HashMap<&'static str, usize>>,
+    passwords: Arc<Passwords>,
 }
 
 pub struct RegistryBuilder {
     collectors: Vec<Box<dyn Collector>>,
+    passwords: Passwords,
 }
 
 impl RegistryBuilder {
@@ -33,16 +77,27 @@ impl RegistryBuilder {
         self
     }
 
-    pub fn build(self) -> anyhow::Result<Registry> {
-        let mut collectors = HashMap::new();
-        for collector in self.collectors {
+    /// Passwords to try on encrypted files.
+    #[must_use]
+    pub fn passwords(mut self, passwords: Passwords) -> Self {
+        self.passwords = passwords;
+        self
+    }
+
+    pub fn build(mut self) -> anyhow::Result<Registry> {
+        // stable sort keeps the registration order in each tier
+        self.collectors.sort_by_key(|c| c.is_fallback());
+        let mut names = HashMap::new();
+        for (i, collector) in self.collectors.iter().enumerate() {
             let name = collector.name();
-            if collectors.insert(name, collector).is_some() {
+            if names.insert(name, i).is_some() {
                 return Err(anyhow!("collector {} already registered", name));
             }
         }
         Ok(Registry {
-            collectors: Arc::new(collectors),
+            collectors: Arc::new(self.collectors),
+            names: Arc::new(names),
+            passwords: Arc::new(self.passwords),
         })
     }
 }
@@ -51,11 +106,12 @@ impl Registry {
     pub fn builder() -> RegistryBuilder {
         RegistryBuilder {
             collectors: Vec::new(),
+            passwords: Passwords::default(),
         }
     }
 
     pub fn get(&self, name: &str) -> Option<&dyn Collector> {
-        self.collectors.get(name).map(|c| &**c)
+        self.names.get(name).map(|&i| &*self.collectors[i])
     }
 
     pub fn must_get(&self, name: &str) -> &dyn Collector {
@@ -64,18 +120,280 @@ impl Registry {
         })
     }
 
-    pub fn collect(&self, path: impl AsRef<Path>) -> Option<(&'static str, Vec<Line>)> {
-        self.collectors
-            .values()
-            .filter_map(|c| {
-                let collector = c.as_ref();
-                if collector.should_collect(path.as_ref()).ok()? {
-                    let content = collector.collect(path.as_ref()).ok()?;
-                    Some((collector.name(), content))
-                } else {
-                    None
+    /// Collects lines of the file by the first accepting collector in dispatching order,
+    /// collectors accepting the content type sniffed by magic bytes are tried before extensions.
+    /// Compressed files are decompressed and dispatched by the inner file name.
+    ///
+    /// Once a collector fails the next one is tried, returns `Ok(None)` if no collector accepts
+    /// the file and a [`CollectError`] if all of them fail.
+    pub fn collect(&self, path: impl AsRef<Path>) -> anyhow::Result<Option<Collected>> {
+        let origin = path.as_ref().to_string_lossy();
+        self.collect_as(path.as_ref(), &origin)
+    }
+
+    /// Collects the file as `origin`, the path matched by password rules,
+    /// e.g. the virtual path of an archive entry spooled to a temporary file.
+    pub fn collect_as(
+        &self,
+        path: impl AsRef<Path>,
+        origin: &str,
+    ) -> anyhow::Result<Option<Collected>> {
+        let decompressed = decompress(path.as_ref())?;
+        let path = decompressed.as_ref().map_or(path.as_ref(), |f| f.path());
+        // the head of the file is read once to sniff the content type and the encoding,
+        // unreadable files are left to collectors to fail
+        let sample = sample(path).unwrap_or_default();
+        let content_type = sniff(&sample);
+        let by_content = self
+            .collectors
+            .iter()
+            .filter(|c| content_type.map_or(false, |t| c.accept_mime_type(t.mime_type)));
+        let by_extension = self
+            .collectors
+            .iter()
+            .filter(|c| c.should_collect(path).unwrap_or(false));
+        let mut tried = HashSet::new();
+        let mut failures = Vec::new();
+        for collector in by_content.chain(by_extension) {
+            let name = collector.name();
+            if !tried.insert(name) {
+                continue;
+            }
+            let collected = self
+                .collect_by(&**collector, path, origin)
+                .map(|lines| Collected {
+                    collector: name,
+                    encoding: collector.encoding(&sample),
+                    lines,
+                });
+            match collected {
+                Ok(collected) => return Ok(Some(collected)),
+                Err(err) => {
+                    warn!("collector {} fails on {:?}: {:#}", name, path, err);
+                    failures.push((name, err));
                 }
-            })
-            .next()
+            }
+        }
+        if failures.is_empty() {
+            Ok(None)
+        } else {
+            Err(CollectError { failures }.into())
+        }
+    }
+
+    /// Collects the file by the collector, encrypted files are retried with passwords of origin.
+    fn collect_by(
+        &self,
+        collector: &dyn Collector,
+        path: &Path,
+        origin: &str,
+    ) -> anyhow::Result<Vec<Line>> {
+        let mut result = collector.collect(path);
+        if !matches!(&result, Err(err) if err.downcast_ref() == Some(&Encrypted::NoPassword)) {
+            return result;
+        }
+        for password in self.passwords.candidates(origin) {
+            result = collector.collect_encrypted(path, password);
+            match &result {
+                Err(err) if err.downcast_ref() == Some(&Encrypted::WrongPassword) => continue,
+                _ => break,
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use anyhow::anyhow;
+    use sgrep_collector::{Collector, Encrypted, Line};
+
+    use super::{CollectError, Registry};
+    use crate::password::Passwords;
+
+    struct Mock {
+        name: &'static str,
+        extension: Option<&'static str>,
+        fallback: bool,
+        broken: bool,
+        password: Option<&'static str>,
+    }
+
+    impl Collector for Mock {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn collect(&self, _path: &Path) -> anyhow::Result<Vec<Line>> {
+            if self.broken {
+                Err(anyhow!("{} is broken", self.name))
+            } else if self.password.is_some() {
+                Err(Encrypted::NoPassword.into())
+            } else {
+                Ok(Vec::new())
+            }
+        }
+
+        fn collect_encrypted(&self, _path: &Path, password: &str) -> anyhow::Result<Vec<Line>> {
+            if self.password == Some(password) {
+                Ok(Vec::new())
+            } else {
+                Err(Encrypted::WrongPassword.into())
+            }
+        }
+
+        fn accept_extension(&self, extension: Option<&str>) -> bool {
+            self.extension.map_or(true, |e| extension == Some(e))
+        }
+
+        fn is_fallback(&self) -> bool {
+            self.fallback
+        }
+    }
+
+    fn mock(name: &'static str, extension: Option<&'static str>, fallback: bool) -> Box<Mock> {
+        Box::new(Mock {
+            name,
+            extension,
+            fallback,
+            broken: false,
+            password: None,
+        })
+    }
+
+    fn broken(name: &'static str, extension: Option<&'static str>) -> Box<Mock> {
+        Box::new(Mock {
+            broken: true,
+            ..*mock(name, extension, false)
+        })
+    }
+
+    fn encrypted(name: &'static str, extension: &'static str, password: &'static str) -> Box<Mock> {
+        Box::new(Mock {
+            password: Some(password),
+            ..*mock(name, Some(extension), false)
+        })
+    }
+
+    fn collector_of(registry: &Registry, path: &str) -> Option<&'static str> {
+        registry.collect(path).unwrap().map(|c| c.collector)
+    }
+
+    #[test]
+    fn first_registered_wins() {
+        for _ in 0..16 {
+            let registry = Registry::builder()
+                .register(mock("a", Some("csv"), false))
+                .register(mock("b", Some("csv"), false))
+                .register(mock("c", Some("csv"), false))
+                .build()
+                .unwrap();
+            assert_eq!(collector_of(&registry, "data.csv"), Some("a"));
+        }
+    }
+
+    #[test]
+    fn fallback_after_others() {
+        let registry = Registry::builder()
+            .register(mock("text", None, true))
+            .register(mock("docx", Some("docx"), false))
+            .build()
+            .unwrap();
+        assert_eq!(collector_of(&registry, "report.docx"), Some("docx"));
+        assert_eq!(collector_of(&registry, "notes.txt"), Some("text"));
+    }
+
+    #[test]
+    fn fallback_keeps_registration_order() {
+        let registry = Registry::builder()
+            .register(mock("text", None, true))
+            .register(mock("binary", None, true))
+            .build()
+            .unwrap();
+        assert_eq!(collector_of(&registry, "README"), Some("text"));
+    }
+
+    #[test]
+    fn duplicated_name() {
+        let result = Registry::builder()
+            .register(mock("a", None, false))
+            .register(mock("a", Some("csv"), true))
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn get_by_name() {
+        let registry = Registry::builder()
+            .register(mock("text", None, true))
+            .register(mock("docx", Some("docx"), false))
+            .build()
+            .unwrap();
+        assert!(registry.get("text").unwrap().is_fallback());
+        assert!(!registry.must_get("docx").is_fallback());
+        assert!(registry.get("pdf").is_none());
+    }
+
+    #[test]
+    fn fall_through_on_failure() {
+        let registry = Registry::builder()
+            .register(broken("docx", Some("docx")))
+            .register(mock("text", None, true))
+            .build()
+            .unwrap();
+        assert_eq!(collector_of(&registry, "locked.docx"), Some("text"));
+    }
+
+    #[test]
+    fn all_failed() {
+        let registry = Registry::builder()
+            .register(broken("docx", Some("docx")))
+            .register(broken("text", None))
+            .build()
+            .unwrap();
+        let err = registry.collect("locked.docx").unwrap_err();
+        let err = err.downcast_ref::<CollectError>().unwrap();
+        assert_eq!(err.collector(), Some("docx"));
+        assert_eq!(
+            err.to_string(),
+            "docx: docx is broken; text: text is broken"
+        );
+        assert!(registry.collect("unknown.xyz").is_err());
+    }
+
+    #[test]
+    fn none_accepted() {
+        let registry = Registry::builder()
+            .register(broken("docx", Some("docx")))
+            .build()
+            .unwrap();
+        assert!(registry.collect("notes.txt").unwrap().is_none());
+    }
+
+    #[test]
+    fn encrypted_with_passwords() {
+        let passwords = Passwords::parse(
+            "# comment\n*.xlsx = wrong\nreports/*.pdf = s3cret\nwrong\ncorrect horse",
+        )
+        .unwrap();
+        let registry = Registry::builder()
+            .register(encrypted("pdf", "pdf", "s3cret"))
+            .register(encrypted("sheet", "xlsx", "s3cret"))
+            .register(encrypted("doc", "doc", "correct horse"))
+            .passwords(passwords)
+            .build()
+            .unwrap();
+        assert_eq!(collector_of(&registry, "reports/q1.pdf"), Some("pdf"));
+        assert_eq!(collector_of(&registry, "reports/q1.doc"), Some("doc"));
+        let err = registry.collect("reports/q1.xlsx").unwrap_err();
+        assert_eq!(err.to_string(), "sheet: encrypted, wrong password");
+        let registry = Registry::builder()
+            .register(encrypted("pdf", "pdf", "s3cret"))
+            .build()
+            .unwrap();
+        let err = registry.collect("reports/q1.pdf").unwrap_err();
+        assert_eq!(err.to_string(), "pdf: encrypted, no password");
     }
 }
@@ -3,11 +3,9 @@ use std::path::PathBuf;
 
 use clap::Args;
 use colored::Colorize;
-use sgrep_collector::all_collectors;
 
-use crate::highlight::highlight;
-use crate::registry::Registry;
-use crate::{Command, Engine};
+use crate::index::CollectOptions;
+use crate::{index, Command, Engine};
 
 /// Fuzzy search words
 #[derive(Debug, PartialEq, Args)]
@@ -24,29 +22,30 @@ pub struct Search {
     query: String,
 
     /// Paths to index and match, supports [glob](https://github.com/rust-lang-nursery/glob)
+    /// and entries of archives like `backup.zip!/docs/*.pdf`
     #[clap(default_value = "*")]
     paths: Vec<String>,
+
+    #[clap(flatten)]
+    options: CollectOptions,
 }
 
 impl Command for Search {
     fn run(&self, index_dir: PathBuf) -> anyhow::Result<()> {
-        let registry = Registry::builder()
-            .register_list(all_collectors())
-            .build()?;
-        let mut engine = Engine::init(index_dir, registry, None)?;
+        let mut engine = Engine::init(index_dir, self.options.registry()?, None)?;
         let paths: HashSet<&str> = self.paths.iter().map(|s| s.as_str()).collect();
         if self.indexing {
-            engine.indexing(paths.clone())?;
+            index::indexing(&mut engine, paths.clone())?;
         }
 
-        let (docs, snippet_generator) = engine.search(&self.query, self.limit, paths)?;
+        let (docs, highlighter) = engine.search(&self.query, self.limit, paths)?;
         for d in docs {
             let doc = d?;
             let path = doc.path().unwrap();
             let collector = doc.collector().unwrap();
             println!("{}({})", path.purple(), collector.yellow().italic());
             for (p, l) in doc.lines() {
-                if let Some(highlighted_line) = highlight(&snippet_generator, l) {
+                if let Some(highlighted_line) = highlighter.highlight(l) {
                     println!("{}:{}", p.green(), highlighted_line);
                 }
             }